@@ -0,0 +1,15 @@
+-- A row either revokes a single access token (jti) or every access token a
+-- user was issued before a point in time (issued_before). Rows are only kept
+-- until the tokens they cover would have expired anyway.
+CREATE TABLE IF NOT EXISTS token_revocations (
+    id SERIAL PRIMARY KEY,
+    jti TEXT UNIQUE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    issued_before TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (jti IS NOT NULL OR issued_before IS NOT NULL)
+);
+
+CREATE INDEX IF NOT EXISTS token_revocations_user_id_idx ON token_revocations (user_id);
+CREATE INDEX IF NOT EXISTS token_revocations_expires_at_idx ON token_revocations (expires_at);
@@ -0,0 +1,57 @@
+CREATE TABLE IF NOT EXISTS roles (
+    name TEXT PRIMARY KEY,
+    description TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS permissions (
+    name TEXT PRIMARY KEY,
+    description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role TEXT NOT NULL REFERENCES roles (name) ON DELETE CASCADE ON UPDATE CASCADE,
+    permission TEXT NOT NULL REFERENCES permissions (name) ON DELETE CASCADE,
+    PRIMARY KEY (role, permission)
+);
+
+INSERT INTO roles (name, description) VALUES
+    ('admin', 'Full access'),
+    ('user', 'Writes and manages their own articles'),
+    ('editor', 'Can edit anyone''s articles'),
+    ('moderator', 'Can remove anyone''s articles and look up users')
+ON CONFLICT (name) DO NOTHING;
+
+-- Keep whatever roles are already in use valid for the foreign key below.
+INSERT INTO roles (name) SELECT DISTINCT role FROM users ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO permissions (name, description) VALUES
+    ('article:create', 'Create articles'),
+    ('article:update:any', 'Edit articles written by anyone'),
+    ('article:delete:any', 'Delete articles written by anyone'),
+    ('user:read:any', 'See other users'' account data'),
+    ('user:update:any', 'Change other users'' account data'),
+    ('role:manage', 'Create roles, change their permissions and assign them'),
+    ('mfa:policy:manage', 'Decide which roles must use two-factor authentication')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role, permission)
+SELECT 'admin', name FROM permissions
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+    ('user', 'article:create'),
+    ('editor', 'article:create'),
+    ('editor', 'article:update:any'),
+    ('moderator', 'article:create'),
+    ('moderator', 'article:delete:any'),
+    ('moderator', 'user:read:any')
+ON CONFLICT DO NOTHING;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'users_role_fkey') THEN
+        ALTER TABLE users ADD CONSTRAINT users_role_fkey
+            FOREIGN KEY (role) REFERENCES roles (name) ON UPDATE CASCADE;
+    END IF;
+END $$;
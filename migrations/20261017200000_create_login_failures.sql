@@ -0,0 +1,16 @@
+-- Failed logins per "user:<username>" and per "ip:<address>". Usernames are
+-- tracked whether or not they exist, so lockouts don't reveal which ones do.
+CREATE TABLE IF NOT EXISTS login_failures (
+    key TEXT PRIMARY KEY,
+    failures INTEGER NOT NULL DEFAULT 0,
+    last_failure_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMPTZ
+);
+
+INSERT INTO permissions (name, description) VALUES
+    ('user:unlock', 'Lift a login lockout')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+    ('admin', 'user:unlock')
+ON CONFLICT DO NOTHING;
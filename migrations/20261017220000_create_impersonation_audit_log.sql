@@ -0,0 +1,23 @@
+-- Every request made with an impersonation token, including the ones that
+-- were refused, kept for as long as the users involved exist.
+CREATE TABLE IF NOT EXISTS impersonation_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    admin_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    jti TEXT NOT NULL,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    allowed BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS impersonation_audit_log_admin_id_idx ON impersonation_audit_log (admin_id);
+CREATE INDEX IF NOT EXISTS impersonation_audit_log_user_id_idx ON impersonation_audit_log (user_id);
+
+INSERT INTO permissions (name, description) VALUES
+    ('user:impersonate', 'Act as another user to reproduce what they see')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+    ('admin', 'user:impersonate')
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,17 @@
+-- "suspended" accounts come back on their own once suspended_until passes,
+-- "banned" ones only through an admin.
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'active'
+        CHECK (status IN ('active', 'suspended', 'banned')),
+    ADD COLUMN IF NOT EXISTS suspended_until TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS status_reason TEXT,
+    ADD COLUMN IF NOT EXISTS hide_articles BOOLEAN NOT NULL DEFAULT FALSE;
+
+INSERT INTO permissions (name, description) VALUES
+    ('user:suspend', 'Suspend, ban and reactivate accounts')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+    ('admin', 'user:suspend'),
+    ('moderator', 'user:suspend')
+ON CONFLICT DO NOTHING;
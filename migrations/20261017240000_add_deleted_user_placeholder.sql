@@ -0,0 +1,13 @@
+-- Articles of deleted accounts that chose to keep them anonymously are moved
+-- to this account. It can't sign in: the password is no valid hash and it is banned.
+INSERT INTO users (username, password, email, role, status, status_reason)
+SELECT 'deleted-user', '!', 'deleted-user@invalid', 'user', 'banned', 'Placeholder author of deleted accounts'
+WHERE NOT EXISTS (SELECT 1 FROM users WHERE username = 'deleted-user');
+
+INSERT INTO permissions (name, description) VALUES
+    ('user:delete:any', 'Delete other users'' accounts')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+    ('admin', 'user:delete:any')
+ON CONFLICT DO NOTHING;
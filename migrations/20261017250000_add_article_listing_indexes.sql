@@ -0,0 +1,13 @@
+-- Keyset pagination needs a value on every row.
+UPDATE articles SET published_on = CURRENT_TIMESTAMP WHERE published_on IS NULL;
+ALTER TABLE articles ALTER COLUMN published_on SET DEFAULT CURRENT_TIMESTAMP;
+ALTER TABLE articles ALTER COLUMN published_on SET NOT NULL;
+
+-- One index per sort order (a b-tree serves both directions) and one for listing by author.
+CREATE INDEX IF NOT EXISTS articles_published_on_id_idx ON articles (published_on, id);
+CREATE INDEX IF NOT EXISTS articles_title_id_idx ON articles (title, id);
+CREATE INDEX IF NOT EXISTS articles_published_by_published_on_id_idx ON articles (published_by, published_on, id);
+
+-- Lets the title substring filter (ILIKE '%...%') use an index.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX IF NOT EXISTS articles_title_trgm_idx ON articles USING GIN (title gin_trgm_ops);
@@ -0,0 +1,34 @@
+-- The text search configuration the index is built with. The app keeps it in sync with
+-- search.language at startup and rebuilds every vector when it changes.
+CREATE TABLE IF NOT EXISTS search_settings (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    language REGCONFIG NOT NULL DEFAULT 'english'
+);
+INSERT INTO search_settings DEFAULT VALUES ON CONFLICT DO NOTHING;
+
+-- Title matches (weight A) rank above content matches (weight B).
+ALTER TABLE articles ADD COLUMN IF NOT EXISTS search_vector TSVECTOR;
+
+CREATE OR REPLACE FUNCTION articles_search_vector_update() RETURNS TRIGGER AS $$
+DECLARE
+    search_language REGCONFIG;
+BEGIN
+    SELECT language INTO search_language FROM search_settings;
+    NEW.search_vector :=
+        setweight(to_tsvector(search_language, COALESCE(NEW.title, '')), 'A') ||
+        setweight(to_tsvector(search_language, COALESCE(NEW.content, '')), 'B');
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS articles_search_vector_trigger ON articles;
+CREATE TRIGGER articles_search_vector_trigger
+    BEFORE INSERT OR UPDATE OF title, content ON articles
+    FOR EACH ROW EXECUTE FUNCTION articles_search_vector_update();
+
+UPDATE articles SET
+    search_vector =
+        setweight(to_tsvector('english', COALESCE(title, '')), 'A') ||
+        setweight(to_tsvector('english', COALESCE(content, '')), 'B');
+
+CREATE INDEX IF NOT EXISTS articles_search_vector_idx ON articles USING GIN (search_vector);
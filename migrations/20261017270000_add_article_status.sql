@@ -0,0 +1,19 @@
+-- New articles start as drafts and go through review, the existing ones are already live.
+-- published_on holds the creation time until an article is approved.
+ALTER TABLE articles
+    ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'published'
+        CHECK (status IN ('draft', 'in_review', 'published', 'archived')),
+    ADD COLUMN IF NOT EXISTS review_comment TEXT;
+ALTER TABLE articles ALTER COLUMN status SET DEFAULT 'draft';
+
+-- The listing mostly asks for published articles in date order.
+CREATE INDEX IF NOT EXISTS articles_status_published_on_id_idx ON articles (status, published_on, id);
+
+INSERT INTO permissions (name, description) VALUES
+    ('article:publish', 'Approve, reject and unpublish articles written by anyone')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+    ('admin', 'article:publish'),
+    ('editor', 'article:publish')
+ON CONFLICT DO NOTHING;
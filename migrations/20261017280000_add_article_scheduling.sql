@@ -0,0 +1,15 @@
+-- The app's sessions always ran in UTC, so that is what the naive values mean.
+ALTER TABLE articles ALTER COLUMN published_on TYPE TIMESTAMPTZ USING published_on AT TIME ZONE 'UTC';
+ALTER TABLE articles ALTER COLUMN published_on SET DEFAULT NOW();
+
+-- Approved articles with a future publish_at wait as "scheduled" until the scheduler
+-- publishes them. Published articles stay hidden from readers until embargo_until.
+ALTER TABLE articles
+    ADD COLUMN IF NOT EXISTS publish_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS embargo_until TIMESTAMPTZ;
+
+ALTER TABLE articles DROP CONSTRAINT IF EXISTS articles_status_check;
+ALTER TABLE articles ADD CONSTRAINT articles_status_check
+    CHECK (status IN ('draft', 'in_review', 'scheduled', 'published', 'archived'));
+
+CREATE INDEX IF NOT EXISTS articles_scheduled_publish_at_idx ON articles (publish_at) WHERE status = 'scheduled';
@@ -0,0 +1,17 @@
+-- Revoking every token of a user bumps token_generation, tokens carry the generation
+-- they were issued under and stop working once it moves on. This replaces
+-- token_revocations.issued_before, whose microseconds never compared cleanly
+-- with the whole seconds of a token's iat.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS token_generation BIGINT NOT NULL DEFAULT 0;
+
+-- Tokens issued before this migration carry no generation, which reads as 0.
+-- Users with a user-wide revocation still in force move past it so those stay revoked.
+UPDATE users SET token_generation = 1
+WHERE id IN (
+    SELECT user_id FROM token_revocations
+    WHERE issued_before IS NOT NULL AND expires_at > NOW()
+);
+
+DELETE FROM token_revocations WHERE jti IS NULL;
+ALTER TABLE token_revocations DROP COLUMN IF EXISTS issued_before;
+ALTER TABLE token_revocations ALTER COLUMN jti SET NOT NULL;
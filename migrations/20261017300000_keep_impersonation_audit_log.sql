@@ -0,0 +1,11 @@
+-- The audit trail outlives the accounts it names: deleting the admin or the
+-- impersonated user clears their id instead of removing the entries.
+ALTER TABLE impersonation_audit_log
+    ALTER COLUMN admin_id DROP NOT NULL,
+    ALTER COLUMN user_id DROP NOT NULL,
+    DROP CONSTRAINT IF EXISTS impersonation_audit_log_admin_id_fkey,
+    DROP CONSTRAINT IF EXISTS impersonation_audit_log_user_id_fkey,
+    ADD CONSTRAINT impersonation_audit_log_admin_id_fkey
+        FOREIGN KEY (admin_id) REFERENCES users (id) ON DELETE SET NULL,
+    ADD CONSTRAINT impersonation_audit_log_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE SET NULL;
@@ -0,0 +1,15 @@
+-- The placeholder for deleted accounts is marked by a flag instead of its name,
+-- so a real user who already had the name never receives other people's articles.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS is_placeholder BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The row the earlier migration created, a real account can't have '!' as its password hash.
+UPDATE users SET is_placeholder = TRUE
+WHERE username = 'deleted-user' AND password = '!' AND email = 'deleted-user@invalid';
+
+-- The name was taken when that migration ran, the placeholder gets a free one.
+INSERT INTO users (username, password, email, role, status, status_reason, is_placeholder)
+SELECT name, '!', name || '@invalid', 'user', 'banned', 'Placeholder author of deleted accounts', TRUE
+FROM (SELECT 'deleted-user-' || substr(md5(random()::text), 1, 8) AS name) AS placeholder
+WHERE NOT EXISTS (SELECT 1 FROM users WHERE is_placeholder);
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_placeholder_idx ON users (is_placeholder) WHERE is_placeholder;
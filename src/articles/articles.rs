@@ -1,224 +1,204 @@
-use crate::{AppState, TokenClaims};
+use crate::auth::permissions::{Authorized, Permission};
+use crate::error::AppError;
+use crate::AppState;
 use actix_web::{
     get, post,delete,put,
-    web::{Data, Json, ReqData, Path},
-    HttpResponse, Responder,
+    web::{Data, Json, Path, Query},
+    HttpResponse,
 };
-use sqlx::{self};
+use sqlx::{self, PgPool, Postgres, QueryBuilder};
 
-use crate::articles::models::{CreateArticleBody, Article, UpdateArticleBody};
+use crate::articles::lifecycle::{find_article, require_editable, require_future};
+use crate::articles::models::{
+    Article, ArticleListQuery, ArticlePage, CreateArticleBody, UpdateArticleBody, Viewer,
+};
+use crate::articles::pagination::{
+    decode_cursor, encode_cursor, order_by, page_size, push_cursor, push_filters, Cursor,
+};
+use crate::users::email_verification::is_verified;
 
 
 
 #[post("/article")]
 async fn create_article(
     state: Data<AppState>,
-    req_user: Option<ReqData<TokenClaims>>,
+    auth: Authorized,
     body: Json<CreateArticleBody>,
-) -> impl Responder {
-    match req_user {
-        Some(user) => {
-            let article: CreateArticleBody = body.into_inner();
-
-            match sqlx::query_as::<_, Article>(
-                "INSERT INTO articles (title, content, published_by)
-                VALUES ($1, $2, $3)
-                RETURNING id, title, content, published_by, published_on",
-            )
-            .bind(article.title)
-            .bind(article.content)
-            .bind(user.id)
-            .fetch_one(&state.db)
-            .await
-            {
-                Ok(articles) => HttpResponse::Ok().json(articles),
-                Err(error) => HttpResponse::InternalServerError().json(format!("{:?}", error)),
-            }
-        }
-        _ => HttpResponse::Unauthorized().json("Unable to verify identity"),
+) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::ArticleCreate)?;
+
+    if !is_verified(&state, auth.id()).await? {
+        return Err(AppError::forbidden("Verify your email address before publishing articles"));
     }
+
+    let article: CreateArticleBody = body.into_inner();
+    require_future(article.publish_at)?;
+
+    let article = sqlx::query_as::<_, Article>(
+        "INSERT INTO articles (title, content, published_by, publish_at, embargo_until)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, title, content, published_by, published_on, status, review_comment,
+            publish_at, embargo_until",
+    )
+    .bind(article.title)
+    .bind(article.content)
+    .bind(auth.id())
+    .bind(article.publish_at)
+    .bind(article.embargo_until)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(article))
 }
 
 
 #[get("/article/{id}")]
 async fn get_article(
     state: Data<AppState>,
+    auth: Authorized,
     article_id: Path<i32>,
-) -> impl Responder {
-    let article_id = article_id.into_inner();
+) -> Result<HttpResponse, AppError> {
+    let article = find_article(&state.db, article_id.into_inner()).await?;
 
-    match sqlx::query_as::<_, Article>(
-        "SELECT id, title, content, published_by, published_on FROM articles WHERE id = $1"
-    )
-    .bind(article_id)
-    .fetch_one(&state.db)
-    .await
-    {
-        Ok(article) => HttpResponse::Ok().json(article),
-        Err(sqlx::Error::RowNotFound) => HttpResponse::NotFound().json("Article not found"),
-        Err(error) => HttpResponse::InternalServerError().json(format!("Database error: {:?}", error)),
+    // Unpublished articles don't exist for those who may not see them.
+    if !Viewer::of(&auth).can_see(&article) {
+        return Err(AppError::not_found("Article not found"));
     }
+
+    Ok(HttpResponse::Ok().json(article))
 }
 
 
 #[get("/articles")]
 async fn get_all_articles(
     state: Data<AppState>,
-) -> impl Responder {
-    match sqlx::query_as::<_, Article>(
-        "SELECT id, title, content, published_by, published_on FROM articles"
-    )
-    .fetch_all(&state.db)
-    .await
-    {
-        Ok(articles) => HttpResponse::Ok().json(articles),
-        Err(error) => HttpResponse::InternalServerError().json(format!("Database error: {:?}", error)),
+    auth: Authorized,
+    query: Query<ArticleListQuery>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    let viewer = Viewer::of(&auth);
+    let limit = page_size(query.limit)?;
+
+    let cursor = query.cursor.as_deref().map(decode_cursor::<Cursor>).transpose()?;
+
+    let mut select = QueryBuilder::<Postgres>::new(
+        "SELECT articles.id, articles.title, articles.content, articles.published_by, articles.published_on,
+            articles.status, articles.review_comment, articles.publish_at, articles.embargo_until
+        FROM articles",
+    );
+    push_filters(&mut select, &query.filters(viewer));
+    if let Some(cursor) = &cursor {
+        push_cursor(&mut select, cursor, query.sort)?;
     }
+    // One extra row tells whether there is a next page.
+    select.push(order_by(query.sort)).push(" LIMIT ").push_bind(limit + 1);
+
+    let mut articles = select.build_query_as::<Article>().fetch_all(&state.db).await?;
+
+    let next_cursor = if articles.len() as i64 > limit {
+        articles.truncate(limit as usize);
+        articles.last().map(|article| encode_cursor(&Cursor::after(article, query.sort)))
+    } else {
+        None
+    };
+
+    let total = if query.include_total {
+        let mut count = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM articles");
+        push_filters(&mut count, &query.filters(viewer));
+        Some(count.build_query_scalar::<i64>().fetch_one(&state.db).await?)
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(ArticlePage {
+        items: articles,
+        next_cursor,
+        total,
+    }))
+}
+
+async fn article_author(db: &PgPool, article_id: i32) -> Result<i32, AppError> {
+    sqlx::query_scalar::<_, i32>("SELECT published_by FROM articles WHERE id = $1")
+        .bind(article_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::not_found("Article not found"))
 }
 
 
 #[delete("/article/{id}")]
 async fn delete_article(
     state: Data<AppState>,
-    req_user: Option<ReqData<TokenClaims>>,
+    auth: Authorized,
     article_id: Path<i32>,
-) -> impl Responder {
-    if let Some(user) = req_user {
-        let article_id = article_id.into_inner();
+) -> Result<HttpResponse, AppError> {
+    let article_id = article_id.into_inner();
 
-        match sqlx::query_as::<_, (i32,)>(
-            "SELECT published_by FROM articles WHERE id = $1"
-        )
-        .bind(article_id)
-        .fetch_optional(&state.db)
-        .await
-        {
-            Ok(Some((published_by,))) => {
-                if published_by == user.id || user.role == "admin" {
-                    match sqlx::query(
-                        "DELETE FROM articles WHERE id = $1"
-                    )
-                    .bind(article_id)
-                    .execute(&state.db)
-                    .await
-                    {
-                        Ok(_) => HttpResponse::Ok().json("Article deleted successfully"),
-                        Err(error) => HttpResponse::InternalServerError().json(format!("Database error: {:?}", error)),
-                    }
-                } else {
-                    HttpResponse::Forbidden().json("You can only delete your own articles")
-                }
-            }
-            Ok(None) => HttpResponse::NotFound().json("Article not found"),
-            Err(error) => HttpResponse::InternalServerError().json(format!("Database error: {:?}", error)),
-        }
-    } else {
-        HttpResponse::Unauthorized().json("Unable to verify identity")
+    let published_by = article_author(&state.db, article_id).await?;
+    if !auth.owns_or_can(published_by, Permission::ArticleDeleteAny) {
+        return Err(AppError::forbidden("You can only delete your own articles"));
     }
+
+    sqlx::query(
+        "DELETE FROM articles WHERE id = $1"
+    )
+    .bind(article_id)
+    .execute(&state.db)
+    .await?;
+
+    Ok(HttpResponse::Ok().json("Article deleted successfully"))
 }
 
 
 #[put("/article/{id}/title")]
 async fn update_article_title(
     state: Data<AppState>,
-    req_user: Option<ReqData<TokenClaims>>,
+    auth: Authorized,
     article_id: Path<i32>,
     body: Json<UpdateArticleBody>,
-) -> impl Responder {
-    if let Some(user) = req_user {
-        let article_id = article_id.into_inner();
-        let updated_article = body.into_inner();
-
-        match sqlx::query_as::<_, (i32,)>("SELECT published_by FROM articles WHERE id = $1")
-            .bind(&article_id)
-            .fetch_optional(&state.db)
-            .await
-        {
-            Ok(Some((published_by,))) => {
-                if published_by == user.id || user.role == "admin" {
-                    match sqlx::query(
-                        "UPDATE articles SET title = $1 WHERE id = $2"
-                    )
-                    .bind(&updated_article.title)
-                    .bind(&article_id)
-                    .execute(&state.db)
-                    .await
-                    {
-                        Ok(_) => {
-                            match sqlx::query_as::<_, Article>(
-                                "SELECT id, title, content, published_by, published_on FROM articles WHERE id = $1"
-                            )
-                            .bind(&article_id)
-                            .fetch_one(&state.db)
-                            .await
-                            {
-                                Ok(updated_article) => HttpResponse::Ok().json(updated_article),
-                                Err(error) => HttpResponse::InternalServerError().json(format!("Failed to fetch updated article: {:?}", error)),
-                            }
-                        },
-                        Err(error) => HttpResponse::InternalServerError().json(format!("Failed to update title: {:?}", error)),
-                    }
-                } else {
-                    HttpResponse::Forbidden().json("You can only update your own articles")
-                }
-            },
-            Ok(None) => HttpResponse::NotFound().json("Article not found"),
-            Err(error) => HttpResponse::InternalServerError().json(format!("Database error: {:?}", error)),
-        }
-    } else {
-        HttpResponse::Unauthorized().json("Unable to verify identity")
-    }
+) -> Result<HttpResponse, AppError> {
+    let article_id = article_id.into_inner();
+    let updated_article = body.into_inner();
+
+    let article = find_article(&state.db, article_id).await?;
+    require_editable(&auth, &article)?;
+
+    let updated_article = sqlx::query_as::<_, Article>(
+        "UPDATE articles SET title = $1 WHERE id = $2
+        RETURNING id, title, content, published_by, published_on, status, review_comment,
+            publish_at, embargo_until"
+    )
+    .bind(&updated_article.title)
+    .bind(article_id)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(updated_article))
 }
 
 
 #[put("/article/{id}/content")]
 async fn update_article_content(
     state: Data<AppState>,
-    req_user: Option<ReqData<TokenClaims>>,
+    auth: Authorized,
     article_id: Path<i32>,
     body: Json<UpdateArticleBody>,
-) -> impl Responder {
-    if let Some(user) = req_user {
-        let article_id = article_id.into_inner();
-        let updated_article = body.into_inner();
-
-        match sqlx::query_as::<_, (i32,)>("SELECT published_by FROM articles WHERE id = $1")
-            .bind(&article_id)
-            .fetch_optional(&state.db)
-            .await
-        {
-            Ok(Some((published_by,))) => {
-                if published_by == user.id || user.role == "admin" {
-                    match sqlx::query(
-                        "UPDATE articles SET content = $1 WHERE id = $2"
-                    )
-                    .bind(&updated_article.content)
-                    .bind(&article_id)
-                    .execute(&state.db)
-                    .await
-                    {
-                        Ok(_) => {
-                            match sqlx::query_as::<_, Article>(
-                                "SELECT id, title, content, published_by, published_on FROM articles WHERE id = $1"
-                            )
-                            .bind(&article_id)
-                            .fetch_one(&state.db)
-                            .await
-                            {
-                                Ok(updated_article) => HttpResponse::Ok().json(updated_article),
-                                Err(error) => HttpResponse::InternalServerError().json(format!("Failed to fetch updated article: {:?}", error)),
-                            }
-                        },
-                        Err(error) => HttpResponse::InternalServerError().json(format!("Failed to update content: {:?}", error)),
-                    }
-                } else {
-                    HttpResponse::Forbidden().json("You can only update your own articles")
-                }
-            },
-            Ok(None) => HttpResponse::NotFound().json("Article not found"),
-            Err(error) => HttpResponse::InternalServerError().json(format!("Database error: {:?}", error)),
-        }
-    } else {
-        HttpResponse::Unauthorized().json("Unable to verify identity")
-    }
-}
\ No newline at end of file
+) -> Result<HttpResponse, AppError> {
+    let article_id = article_id.into_inner();
+    let updated_article = body.into_inner();
+
+    let article = find_article(&state.db, article_id).await?;
+    require_editable(&auth, &article)?;
+
+    let updated_article = sqlx::query_as::<_, Article>(
+        "UPDATE articles SET content = $1 WHERE id = $2
+        RETURNING id, title, content, published_by, published_on, status, review_comment,
+            publish_at, embargo_until"
+    )
+    .bind(&updated_article.content)
+    .bind(article_id)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(updated_article))
+}
@@ -0,0 +1,294 @@
+use actix_web::{
+    post, put,
+    web::{Data, Json, Path},
+    HttpResponse,
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::articles::models::{Article, ArticleStatus, RejectArticleBody, ScheduleArticleBody};
+use crate::auth::permissions::{Authorized, Permission};
+use crate::error::AppError;
+use crate::AppState;
+
+/// The article as it is now, `NotFound` when it doesn't exist.
+pub async fn find_article(db: &PgPool, article_id: i32) -> Result<Article, AppError> {
+    sqlx::query_as::<_, Article>(
+        "SELECT id, title, content, published_by, published_on, status, review_comment,
+            publish_at, embargo_until
+        FROM articles WHERE id = $1",
+    )
+    .bind(article_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::not_found("Article not found"))
+}
+
+/// Moves the article on from the status it was read with. Approving stamps
+/// `published_on`, every transition replaces the review comment.
+async fn transition(
+    db: &PgPool,
+    article: &Article,
+    to: ArticleStatus,
+    comment: Option<String>,
+) -> Result<Article, AppError> {
+    sqlx::query_as::<_, Article>(
+        "UPDATE articles SET
+            status = $1,
+            review_comment = $2,
+            published_on = CASE WHEN $1 = 'published' THEN CURRENT_TIMESTAMP ELSE published_on END
+        WHERE id = $3 AND status = $4
+        RETURNING id, title, content, published_by, published_on, status, review_comment,
+            publish_at, embargo_until",
+    )
+    .bind(to)
+    .bind(comment)
+    .bind(article.id)
+    .bind(article.status)
+    .fetch_optional(db)
+    .await?
+    // Someone else moved it between the read and the update.
+    .ok_or_else(|| AppError::conflict("The article changed status in the meantime, reload it and try again"))
+}
+
+/// Authors change their articles while they are drafts or archived, changes to
+/// anything under review or live need `article:update:any`.
+pub fn require_editable(auth: &Authorized, article: &Article) -> Result<(), AppError> {
+    if auth.can(Permission::ArticleUpdateAny) {
+        return Ok(());
+    }
+    if article.published_by != auth.id() {
+        return Err(AppError::forbidden("You can only update your own articles"));
+    }
+    if !matches!(article.status, ArticleStatus::Draft | ArticleStatus::Archived) {
+        return Err(AppError::conflict(format!(
+            "Cannot update an article that is {}, unpublish it or wait for the review",
+            article.status.as_str()
+        )));
+    }
+    Ok(())
+}
+
+pub fn require_future(publish_at: Option<DateTime<Utc>>) -> Result<(), AppError> {
+    if publish_at.is_some_and(|publish_at| publish_at <= Utc::now()) {
+        return Err(AppError::bad_request("publish_at must be in the future"));
+    }
+    Ok(())
+}
+
+fn require_status(article: &Article, allowed: &[ArticleStatus], action: &str) -> Result<(), AppError> {
+    if allowed.contains(&article.status) {
+        Ok(())
+    } else {
+        Err(AppError::conflict(format!(
+            "Cannot {} an article that is {}",
+            action,
+            article.status.as_str()
+        )))
+    }
+}
+
+/// Authors send their drafts, or archived articles they want back online, to review.
+#[post("/article/{id}/submit")]
+async fn submit_article(
+    state: Data<AppState>,
+    auth: Authorized,
+    article_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let article = find_article(&state.db, article_id.into_inner()).await?;
+
+    if article.published_by != auth.id() {
+        return Err(AppError::forbidden("Only the author can submit an article for review"));
+    }
+    require_status(&article, &[ArticleStatus::Draft, ArticleStatus::Archived], "submit")?;
+
+    let article = transition(&state.db, &article, ArticleStatus::InReview, None).await?;
+
+    Ok(HttpResponse::Ok().json(article))
+}
+
+/// Reviewers publish articles waiting for review, never their own. Articles
+/// with a future `publish_at` are scheduled and the scheduler publishes them.
+#[post("/article/{id}/approve")]
+async fn approve_article(
+    state: Data<AppState>,
+    auth: Authorized,
+    article_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::ArticlePublish)?;
+
+    let article = find_article(&state.db, article_id.into_inner()).await?;
+
+    if article.published_by == auth.id() {
+        return Err(AppError::forbidden("Another reviewer has to approve your own articles"));
+    }
+    require_status(&article, &[ArticleStatus::InReview], "approve")?;
+
+    let to = if article.publish_at.is_some_and(|publish_at| publish_at > Utc::now()) {
+        ArticleStatus::Scheduled
+    } else {
+        ArticleStatus::Published
+    };
+    let article = transition(&state.db, &article, to, None).await?;
+
+    Ok(HttpResponse::Ok().json(article))
+}
+
+/// Reviewers send an article back to its author as a draft, saying why.
+#[post("/article/{id}/reject")]
+async fn reject_article(
+    state: Data<AppState>,
+    auth: Authorized,
+    article_id: Path<i32>,
+    body: Json<RejectArticleBody>,
+) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::ArticlePublish)?;
+
+    let comment = body.into_inner().comment.trim().to_string();
+    if comment.is_empty() {
+        return Err(AppError::bad_request("A rejection needs a comment for the author"));
+    }
+
+    let article = find_article(&state.db, article_id.into_inner()).await?;
+
+    if article.published_by == auth.id() {
+        return Err(AppError::forbidden("Another reviewer has to review your own articles"));
+    }
+    require_status(&article, &[ArticleStatus::InReview], "reject")?;
+
+    let article = transition(&state.db, &article, ArticleStatus::Draft, Some(comment)).await?;
+
+    Ok(HttpResponse::Ok().json(article))
+}
+
+/// Takes a published article offline or cancels a scheduled one, by its author or a reviewer.
+#[post("/article/{id}/unpublish")]
+async fn unpublish_article(
+    state: Data<AppState>,
+    auth: Authorized,
+    article_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let article = find_article(&state.db, article_id.into_inner()).await?;
+
+    if !auth.owns_or_can(article.published_by, Permission::ArticlePublish) {
+        return Err(AppError::forbidden("You can only unpublish your own articles"));
+    }
+    require_status(&article, &[ArticleStatus::Scheduled, ArticleStatus::Published], "unpublish")?;
+
+    let article = transition(&state.db, &article, ArticleStatus::Archived, None).await?;
+
+    Ok(HttpResponse::Ok().json(article))
+}
+
+/// Sets when the article goes live and until when readers can't see it. Reviewers
+/// may move the times of scheduled and published articles, authors only while
+/// they may edit.
+#[put("/article/{id}/schedule")]
+async fn schedule_article(
+    state: Data<AppState>,
+    auth: Authorized,
+    article_id: Path<i32>,
+    body: Json<ScheduleArticleBody>,
+) -> Result<HttpResponse, AppError> {
+    let body = body.into_inner();
+    let article = find_article(&state.db, article_id.into_inner()).await?;
+
+    if !auth.can(Permission::ArticlePublish) {
+        require_editable(&auth, &article)?;
+    }
+    require_future(body.publish_at)?;
+
+    // Publishing already happened, only the embargo still means something.
+    let publish_at = if article.status == ArticleStatus::Published {
+        article.publish_at
+    } else {
+        body.publish_at
+    };
+
+    let article = sqlx::query_as::<_, Article>(
+        "UPDATE articles SET publish_at = $1, embargo_until = $2 WHERE id = $3
+        RETURNING id, title, content, published_by, published_on, status, review_comment,
+            publish_at, embargo_until",
+    )
+    .bind(publish_at)
+    .bind(body.embargo_until)
+    .bind(article.id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::not_found("Article not found"))?;
+
+    Ok(HttpResponse::Ok().json(article))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::ResponseError;
+    use chrono::Duration;
+
+    use crate::articles::models::Viewer;
+
+    const AUTHOR: i32 = 1;
+
+    fn article(status: ArticleStatus, embargo_until: Option<DateTime<Utc>>) -> Article {
+        Article {
+            id: 1,
+            title: "Title".to_string(),
+            content: "Content".to_string(),
+            published_by: AUTHOR,
+            published_on: Utc::now(),
+            status,
+            review_comment: None,
+            publish_at: None,
+            embargo_until,
+        }
+    }
+
+    #[test]
+    fn who_sees_and_edits_an_article_in_each_state() {
+        let author = Authorized::with_permissions(AUTHOR, vec![Permission::ArticleCreate]);
+        let editor = Authorized::with_permissions(
+            2,
+            vec![Permission::ArticleCreate, Permission::ArticleUpdateAny, Permission::ArticlePublish],
+        );
+        // Any other signed-in user, without permissions on articles.
+        let anonymous = Authorized::with_permissions(3, vec![]);
+
+        let embargoed = Some(Utc::now() + Duration::hours(1));
+        let embargo_over = Some(Utc::now() - Duration::hours(1));
+
+        // Who sees it (author, editor, anonymous) and the status code of an
+        // edit, 200 when it's allowed.
+        let cases = [
+            ("draft", article(ArticleStatus::Draft, None), [true, false, false], [200, 200, 403]),
+            ("in review", article(ArticleStatus::InReview, None), [true, true, false], [409, 200, 403]),
+            ("scheduled", article(ArticleStatus::Scheduled, None), [true, true, false], [409, 200, 403]),
+            ("published", article(ArticleStatus::Published, None), [true, true, true], [409, 200, 403]),
+            ("embargoed", article(ArticleStatus::Published, embargoed), [true, true, false], [409, 200, 403]),
+            ("embargo over", article(ArticleStatus::Published, embargo_over), [true, true, true], [409, 200, 403]),
+            ("archived", article(ArticleStatus::Archived, None), [true, false, false], [200, 200, 403]),
+        ];
+
+        for (name, article, visible, edit) in cases {
+            for (index, (viewer, auth)) in [("author", &author), ("editor", &editor), ("anonymous", &anonymous)]
+                .into_iter()
+                .enumerate()
+            {
+                assert_eq!(Viewer::of(auth).can_see(&article), visible[index], "{} article, {}", name, viewer);
+
+                let status = match require_editable(auth, &article) {
+                    Ok(()) => 200,
+                    Err(error) => error.status_code().as_u16(),
+                };
+                assert_eq!(status, edit[index], "editing a {} article as {}", name, viewer);
+            }
+        }
+    }
+
+    #[test]
+    fn scheduling_needs_a_time_in_the_future() {
+        assert!(require_future(None).is_ok());
+        assert!(require_future(Some(Utc::now() + Duration::minutes(5))).is_ok());
+        assert!(require_future(Some(Utc::now() - Duration::minutes(5))).is_err());
+    }
+}
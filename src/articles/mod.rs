@@ -1,4 +1,12 @@
+#[allow(clippy::module_inception)]
 pub mod articles;
+pub mod lifecycle;
 pub mod models;
+pub mod pagination;
+pub mod scheduler;
+pub mod search;
 
-pub use articles::{create_article,get_all_articles,get_article,delete_article,update_article_content,update_article_title};
\ No newline at end of file
+pub use articles::{create_article,get_all_articles,get_article,delete_article,update_article_content,update_article_title};
+pub use search::{search_articles, sync_search_language};
+pub use lifecycle::{approve_article, reject_article, schedule_article, submit_article, unpublish_article};
+pub use scheduler::ArticleScheduler;
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use sqlx::{self, FromRow};
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::auth::permissions::{Authorized, Permission};
 
 
 
@@ -8,6 +10,34 @@ use chrono::NaiveDateTime;
 pub struct CreateArticleBody {
     pub title: String,
     pub content: String,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub embargo_until: Option<DateTime<Utc>>,
+}
+
+/// Where an article is in its lifecycle. Authors write drafts and submit them,
+/// reviewers approve or reject them, published articles can be archived again.
+/// Approved articles with a future `publish_at` wait as scheduled.
+#[derive(Deserialize, Serialize, sqlx::Type, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum ArticleStatus {
+    Draft,
+    InReview,
+    Scheduled,
+    Published,
+    Archived,
+}
+
+impl ArticleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArticleStatus::Draft => "draft",
+            ArticleStatus::InReview => "in_review",
+            ArticleStatus::Scheduled => "scheduled",
+            ArticleStatus::Published => "published",
+            ArticleStatus::Archived => "archived",
+        }
+    }
 }
 
 #[derive(Serialize, FromRow)]
@@ -16,7 +46,15 @@ pub struct Article {
    pub title: String,
    pub content: String,
    pub published_by: i32,
-   pub published_on: Option<NaiveDateTime>,
+   pub published_on: DateTime<Utc>,
+   pub status: ArticleStatus,
+   /// Why the last review sent the article back to draft.
+   #[serde(skip_serializing_if = "Option::is_none")]
+   pub review_comment: Option<String>,
+   /// When an approved article goes live, right away when unset.
+   pub publish_at: Option<DateTime<Utc>>,
+   /// Readers don't see the published article before this.
+   pub embargo_until: Option<DateTime<Utc>>,
 }
 
 
@@ -24,4 +62,146 @@ pub struct Article {
 pub struct UpdateArticleBody {
     pub title: Option<String>,
     pub content: Option<String>,
-}
\ No newline at end of file
+}
+
+#[derive(Deserialize)]
+pub struct RejectArticleBody {
+    pub comment: String,
+}
+
+/// Replaces both times, leaving one out clears it.
+#[derive(Deserialize)]
+pub struct ScheduleArticleBody {
+    pub publish_at: Option<DateTime<Utc>>,
+    pub embargo_until: Option<DateTime<Utc>>,
+}
+
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ArticleSort {
+    #[default]
+    Newest,
+    Oldest,
+    Title,
+}
+
+/// Query string of `GET /articles`. Dates are whole days, `to` included.
+#[derive(Deserialize)]
+pub struct ArticleListQuery {
+    pub author: Option<i32>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// Case-insensitive substring of the title.
+    pub title: Option<String>,
+    /// Only articles in this state, among the ones the caller may see.
+    pub status: Option<ArticleStatus>,
+    #[serde(default)]
+    pub sort: ArticleSort,
+    pub limit: Option<i64>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    /// Also count every match, which costs an extra query.
+    #[serde(default)]
+    pub include_total: bool,
+}
+
+/// Who is looking: everyone sees published articles once their embargo ends,
+/// authors their own in any state and reviewers everything on its way to readers.
+#[derive(Clone, Copy)]
+pub struct Viewer {
+    pub id: i32,
+    pub reviewer: bool,
+}
+
+impl Viewer {
+    pub fn of(auth: &Authorized) -> Self {
+        Viewer {
+            id: auth.id(),
+            reviewer: auth.can(Permission::ArticlePublish),
+        }
+    }
+
+    /// The same rule the listing applies in SQL.
+    pub fn can_see(&self, article: &Article) -> bool {
+        let released = article.status == ArticleStatus::Published
+            && article.embargo_until.is_none_or(|embargo_until| embargo_until <= Utc::now());
+        let reviewable = matches!(
+            article.status,
+            ArticleStatus::InReview | ArticleStatus::Scheduled | ArticleStatus::Published
+        );
+
+        released || article.published_by == self.id || (self.reviewer && reviewable)
+    }
+}
+
+/// The filters the listing and the search share.
+pub struct ArticleFilters<'a> {
+    pub viewer: Viewer,
+    pub author: Option<i32>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub title: Option<&'a str>,
+    pub status: Option<ArticleStatus>,
+}
+
+impl ArticleListQuery {
+    pub fn filters(&self, viewer: Viewer) -> ArticleFilters<'_> {
+        ArticleFilters {
+            viewer,
+            author: self.author,
+            from: self.from,
+            to: self.to,
+            title: self.title.as_deref(),
+            status: self.status,
+        }
+    }
+}
+
+/// Query string of `GET /articles/search`, `q` takes web search syntax:
+/// `"exact phrase"`, `or`, `-excluded`.
+#[derive(Deserialize)]
+pub struct ArticleSearchQuery {
+    pub q: String,
+    pub author: Option<i32>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub title: Option<String>,
+    pub status: Option<ArticleStatus>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub include_total: bool,
+}
+
+impl ArticleSearchQuery {
+    pub fn filters(&self, viewer: Viewer) -> ArticleFilters<'_> {
+        ArticleFilters {
+            viewer,
+            author: self.author,
+            from: self.from,
+            to: self.to,
+            title: self.title.as_deref(),
+            status: self.status,
+        }
+    }
+}
+
+#[derive(Serialize, FromRow)]
+pub struct ArticleSearchResult {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub article: Article,
+    pub rank: f32,
+    /// Matching parts of the content as escaped HTML, the search terms wrapped in `<mark>`.
+    pub snippet: String,
+}
+
+#[derive(Serialize)]
+pub struct ArticlePage<T> {
+    pub items: Vec<T>,
+    /// Pass as `cursor` to get the next page, `null` on the last one.
+    pub next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+}
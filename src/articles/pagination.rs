@@ -0,0 +1,269 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Days, NaiveTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::articles::models::{Article, ArticleFilters, ArticleSort};
+use crate::error::AppError;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// Position after the last article of a listing page: the sort key of that article
+/// plus its id to break ties. Handed out base64 encoded, clients treat it as opaque.
+#[derive(Serialize, Deserialize)]
+pub struct Cursor {
+    pub sort: ArticleSort,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_on: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub id: i32,
+}
+
+impl Cursor {
+    pub fn after(article: &Article, sort: ArticleSort) -> Self {
+        Cursor {
+            sort,
+            published_on: (sort != ArticleSort::Title).then_some(article.published_on),
+            title: (sort == ArticleSort::Title).then(|| article.title.clone()),
+            id: article.id,
+        }
+    }
+}
+
+pub fn encode_cursor<T: Serialize>(cursor: &T) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor).unwrap_or_default())
+}
+
+pub fn decode_cursor<T: DeserializeOwned>(cursor: &str) -> Result<T, AppError> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or_else(|| AppError::bad_request("Invalid cursor"))
+}
+
+pub fn page_size(limit: Option<i64>) -> Result<i64, AppError> {
+    match limit {
+        None => Ok(DEFAULT_PAGE_SIZE),
+        Some(limit) if (1..=MAX_PAGE_SIZE).contains(&limit) => Ok(limit),
+        Some(_) => Err(AppError::bad_request(format!("limit must be between 1 and {}", MAX_PAGE_SIZE))),
+    }
+}
+
+/// Escapes `%`, `_` and `\` so user input only ever matches literally in LIKE.
+fn escape_like(pattern: &str) -> String {
+    pattern.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Appends the conditions every listing shares, starting with `WHERE`.
+/// Articles of banned users who asked to hide them never show up, neither do
+/// the ones the viewer may not see.
+pub fn push_filters(query: &mut QueryBuilder<'_, Postgres>, filters: &ArticleFilters) {
+    query.push(
+        " WHERE NOT EXISTS (
+            SELECT 1 FROM users
+            WHERE users.id = articles.published_by AND users.status = 'banned' AND users.hide_articles
+        )",
+    );
+
+    let viewer = filters.viewer;
+    query
+        .push(
+            " AND ((articles.status = 'published'
+                AND (articles.embargo_until IS NULL OR articles.embargo_until <= NOW()))
+            OR articles.published_by = ",
+        )
+        .push_bind(viewer.id);
+    if viewer.reviewer {
+        query.push(" OR articles.status IN ('in_review', 'scheduled', 'published')");
+    }
+    query.push(")");
+
+    if let Some(status) = filters.status {
+        query.push(" AND articles.status = ").push_bind(status);
+    }
+
+    if let Some(author) = filters.author {
+        query.push(" AND articles.published_by = ").push_bind(author);
+    }
+
+    if let Some(from) = filters.from {
+        query
+            .push(" AND articles.published_on >= ")
+            .push_bind(from.and_time(NaiveTime::MIN).and_utc());
+    }
+
+    if let Some(to) = filters.to.and_then(|to| to.checked_add_days(Days::new(1))) {
+        query
+            .push(" AND articles.published_on < ")
+            .push_bind(to.and_time(NaiveTime::MIN).and_utc());
+    }
+
+    if let Some(title) = filters.title.filter(|title| !title.is_empty()) {
+        query
+            .push(" AND articles.title ILIKE ")
+            .push_bind(format!("%{}%", escape_like(title)));
+    }
+}
+
+/// Appends the keyset condition that skips everything up to the cursor, which
+/// has to come from a listing in the same `sort` order.
+pub fn push_cursor(
+    query: &mut QueryBuilder<'_, Postgres>,
+    cursor: &Cursor,
+    sort: ArticleSort,
+) -> Result<(), AppError> {
+    if cursor.sort != sort {
+        return Err(AppError::bad_request("The cursor belongs to a different sort order"));
+    }
+
+    match (cursor.sort, &cursor.published_on, &cursor.title) {
+        (ArticleSort::Newest, Some(published_on), _) => {
+            query
+                .push(" AND (articles.published_on, articles.id) < (")
+                .push_bind(*published_on)
+                .push(", ")
+                .push_bind(cursor.id)
+                .push(")");
+        }
+        (ArticleSort::Oldest, Some(published_on), _) => {
+            query
+                .push(" AND (articles.published_on, articles.id) > (")
+                .push_bind(*published_on)
+                .push(", ")
+                .push_bind(cursor.id)
+                .push(")");
+        }
+        (ArticleSort::Title, _, Some(title)) => {
+            query
+                .push(" AND (articles.title, articles.id) > (")
+                .push_bind(title.clone())
+                .push(", ")
+                .push_bind(cursor.id)
+                .push(")");
+        }
+        _ => return Err(AppError::bad_request("Invalid cursor")),
+    }
+
+    Ok(())
+}
+
+pub fn order_by(sort: ArticleSort) -> &'static str {
+    match sort {
+        ArticleSort::Newest => " ORDER BY articles.published_on DESC, articles.id DESC",
+        ArticleSort::Oldest => " ORDER BY articles.published_on ASC, articles.id ASC",
+        ArticleSort::Title => " ORDER BY articles.title ASC, articles.id ASC",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    use crate::articles::models::ArticleStatus;
+
+    fn article() -> Article {
+        Article {
+            id: 42,
+            title: "Keyset paging".to_string(),
+            content: String::new(),
+            published_by: 1,
+            published_on: Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap(),
+            status: ArticleStatus::Published,
+            review_comment: None,
+            publish_at: None,
+            embargo_until: None,
+        }
+    }
+
+    fn bad_request(result: Result<impl Sized, AppError>) -> String {
+        match result {
+            Err(AppError::BadRequest(detail)) => detail,
+            Err(error) => panic!("expected a bad request, got {:?}", error),
+            Ok(_) => panic!("expected a bad request"),
+        }
+    }
+
+    #[test]
+    fn cursors_survive_encoding() {
+        for sort in [ArticleSort::Newest, ArticleSort::Oldest, ArticleSort::Title] {
+            let encoded = encode_cursor(&Cursor::after(&article(), sort));
+            let decoded = decode_cursor::<Cursor>(&encoded).unwrap();
+
+            assert!(decoded.sort == sort);
+            assert_eq!(decoded.id, 42);
+            if sort == ArticleSort::Title {
+                assert_eq!(decoded.title.as_deref(), Some("Keyset paging"));
+                assert_eq!(decoded.published_on, None);
+            } else {
+                assert_eq!(decoded.published_on, Some(article().published_on));
+                assert_eq!(decoded.title, None);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_tampered_cursors() {
+        let encoded = encode_cursor(&Cursor::after(&article(), ArticleSort::Newest));
+
+        for tampered in [
+            format!("{}!", encoded),
+            encoded[..encoded.len() - 4].to_string(),
+            URL_SAFE_NO_PAD.encode(br#"{"sort":"newest","id":"42"}"#),
+            URL_SAFE_NO_PAD.encode(br#"{"sort":"random","id":42}"#),
+            String::new(),
+        ] {
+            assert_eq!(bad_request(decode_cursor::<Cursor>(&tampered)), "Invalid cursor", "{}", tampered);
+        }
+    }
+
+    #[test]
+    fn escapes_like_wildcards() {
+        assert_eq!(escape_like("100%"), "100\\%");
+        assert_eq!(escape_like("snake_case"), "snake\\_case");
+        assert_eq!(escape_like("C:\\temp"), "C:\\\\temp");
+        assert_eq!(escape_like("\\%_"), "\\\\\\%\\_");
+        assert_eq!(escape_like("plain title"), "plain title");
+    }
+
+    #[test]
+    fn pushes_the_keyset_condition_of_the_sort_order() {
+        for (sort, condition) in [
+            (ArticleSort::Newest, " AND (articles.published_on, articles.id) < ($1, $2)"),
+            (ArticleSort::Oldest, " AND (articles.published_on, articles.id) > ($1, $2)"),
+            (ArticleSort::Title, " AND (articles.title, articles.id) > ($1, $2)"),
+        ] {
+            let mut query = QueryBuilder::<Postgres>::new("");
+            push_cursor(&mut query, &Cursor::after(&article(), sort), sort).unwrap();
+            assert_eq!(query.sql(), condition);
+        }
+    }
+
+    #[test]
+    fn rejects_a_cursor_from_another_sort_order() {
+        let cursor = Cursor::after(&article(), ArticleSort::Newest);
+        let mut query = QueryBuilder::<Postgres>::new("");
+
+        assert_eq!(
+            bad_request(push_cursor(&mut query, &cursor, ArticleSort::Title)),
+            "The cursor belongs to a different sort order"
+        );
+        assert_eq!(query.sql(), "");
+    }
+
+    #[test]
+    fn rejects_a_cursor_without_its_sort_key() {
+        // Well-formed but edited by hand: sorted by title, only a date to go on.
+        let cursor = Cursor {
+            sort: ArticleSort::Title,
+            ..Cursor::after(&article(), ArticleSort::Newest)
+        };
+        let mut query = QueryBuilder::<Postgres>::new("");
+
+        assert_eq!(bad_request(push_cursor(&mut query, &cursor, ArticleSort::Title)), "Invalid cursor");
+    }
+}
@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use actix::{Actor, AsyncContext, Context};
+use sqlx::PgPool;
+
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Publishes every scheduled article whose `publish_at` has come, dated at that
+/// time rather than whenever the scheduler got to it. Returns how many went live.
+pub async fn publish_due(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let published = sqlx::query(
+        "UPDATE articles SET status = 'published', published_on = COALESCE(publish_at, NOW())
+        WHERE status = 'scheduled' AND (publish_at IS NULL OR publish_at <= NOW())",
+    )
+    .execute(db)
+    .await?;
+
+    Ok(published.rows_affected())
+}
+
+/// Checks for due articles right after startup, so nothing waits out downtime,
+/// then every [`SCHEDULE_INTERVAL`].
+pub struct ArticleScheduler {
+    pub db: PgPool,
+}
+
+impl ArticleScheduler {
+    fn run(&self) {
+        let db = self.db.clone();
+        actix::spawn(async move {
+            if let Err(error) = publish_due(&db).await {
+                eprintln!("Failed to publish scheduled articles: {:?}", error);
+            }
+        });
+    }
+}
+
+impl Actor for ArticleScheduler {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.run();
+        ctx.run_interval(SCHEDULE_INTERVAL, |scheduler, _| scheduler.run());
+    }
+}
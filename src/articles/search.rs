@@ -0,0 +1,193 @@
+use actix_web::{
+    get,
+    web::{Data, Query},
+    HttpResponse,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+
+use crate::articles::models::{ArticlePage, ArticleSearchQuery, ArticleSearchResult, Viewer};
+use crate::articles::pagination::{decode_cursor, encode_cursor, page_size, push_filters};
+use crate::auth::permissions::Authorized;
+use crate::error::AppError;
+use crate::AppState;
+
+/// Marks the start and end of a match in the raw headline, swapped for `<mark>`
+/// once the content around them is escaped.
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+
+/// Up to two fragments of the content around the matches, terms wrapped in the markers above.
+const HEADLINE_OPTIONS: &str =
+    "StartSel=\u{2}, StopSel=\u{3}, MaxFragments=2, MaxWords=30, MinWords=10, FragmentDelimiter=\" … \"";
+
+/// Position after the last result of a search page: its rank plus its id to break ties.
+#[derive(Serialize, Deserialize)]
+struct SearchCursor {
+    rank: f32,
+    id: i32,
+}
+
+/// Turns a raw headline into HTML: the stored content is escaped so it can't inject
+/// markup, only the match markers become `<mark>` tags.
+fn render_snippet(headline: &str) -> String {
+    let mut snippet = String::with_capacity(headline.len());
+    for character in headline.chars() {
+        match character {
+            MATCH_START => snippet.push_str("<mark>"),
+            MATCH_END => snippet.push_str("</mark>"),
+            '&' => snippet.push_str("&amp;"),
+            '<' => snippet.push_str("&lt;"),
+            '>' => snippet.push_str("&gt;"),
+            '"' => snippet.push_str("&quot;"),
+            '\'' => snippet.push_str("&#39;"),
+            _ => snippet.push(character),
+        }
+    }
+    snippet
+}
+
+/// Points the search index at `language`, rebuilding every article's vector when it
+/// was built with another configuration. Fails if Postgres doesn't know the language.
+pub async fn sync_search_language(db: &PgPool, language: &str) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let changed = sqlx::query("UPDATE search_settings SET language = $1::regconfig WHERE language <> $1::regconfig")
+        .bind(language)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+        > 0;
+
+    if changed {
+        sqlx::query(
+            "UPDATE articles SET search_vector =
+                setweight(to_tsvector($1::regconfig, COALESCE(title, '')), 'A') ||
+                setweight(to_tsvector($1::regconfig, COALESCE(content, '')), 'B')",
+        )
+        .bind(language)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await
+}
+
+/// Starts the shared part of a search: the filters of the listing plus the text match.
+fn matching<'a>(
+    query: &mut QueryBuilder<'a, Postgres>,
+    search: &'a ArticleSearchQuery,
+    viewer: Viewer,
+    language: &'a str,
+) {
+    query
+        .push(" FROM articles, websearch_to_tsquery(")
+        .push_bind(language)
+        .push("::regconfig, ")
+        .push_bind(search.q.trim())
+        .push(") AS search_query");
+    push_filters(query, &search.filters(viewer));
+    query.push(" AND articles.search_vector @@ search_query");
+}
+
+#[get("/articles/search")]
+async fn search_articles(
+    state: Data<AppState>,
+    auth: Authorized,
+    query: Query<ArticleSearchQuery>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    let viewer = Viewer::of(&auth);
+    if query.q.trim().is_empty() {
+        return Err(AppError::bad_request("q must not be empty"));
+    }
+    let limit = page_size(query.limit)?;
+    let cursor = query.cursor.as_deref().map(decode_cursor::<SearchCursor>).transpose()?;
+    let language = state.config.search.language.as_str();
+
+    // Ranks and pages in the inner query so snippets are only built for the returned rows.
+    let mut select = QueryBuilder::<Postgres>::new(
+        "SELECT matches.id, matches.title, matches.content, matches.published_by, matches.published_on,
+            matches.status, matches.review_comment, matches.publish_at, matches.embargo_until, matches.rank,
+            ts_headline(",
+    );
+    select
+        .push_bind(language)
+        .push("::regconfig, matches.content, matches.search_query, ")
+        .push_bind(HEADLINE_OPTIONS)
+        .push(
+            ") AS snippet
+            FROM (
+                SELECT articles.id, articles.title, articles.content, articles.published_by, articles.published_on,
+                    articles.status, articles.review_comment, articles.publish_at, articles.embargo_until,
+                    ts_rank(articles.search_vector, search_query) AS rank, search_query",
+        );
+    matching(&mut select, &query, viewer, language);
+    if let Some(cursor) = &cursor {
+        select
+            .push(" AND (ts_rank(articles.search_vector, search_query), articles.id) < (")
+            .push_bind(cursor.rank)
+            .push(", ")
+            .push_bind(cursor.id)
+            .push(")");
+    }
+    // One extra row tells whether there is a next page.
+    select
+        .push(" ORDER BY rank DESC, articles.id DESC LIMIT ")
+        .push_bind(limit + 1)
+        .push(") AS matches ORDER BY matches.rank DESC, matches.id DESC");
+
+    let mut results = select.build_query_as::<ArticleSearchResult>().fetch_all(&state.db).await?;
+    for result in &mut results {
+        result.snippet = render_snippet(&result.snippet);
+    }
+
+    let next_cursor = if results.len() as i64 > limit {
+        results.truncate(limit as usize);
+        results.last().map(|result| {
+            encode_cursor(&SearchCursor {
+                rank: result.rank,
+                id: result.article.id,
+            })
+        })
+    } else {
+        None
+    };
+
+    let total = if query.include_total {
+        let mut count = QueryBuilder::<Postgres>::new("SELECT COUNT(*)");
+        matching(&mut count, &query, viewer, language);
+        Some(count.build_query_scalar::<i64>().fetch_one(&state.db).await?)
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(ArticlePage {
+        items: results,
+        next_cursor,
+        total,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_the_content_around_the_matches() {
+        let headline = "<script>alert(\"x\")</script> the \u{2}search\u{3} & 'more'";
+
+        assert_eq!(
+            render_snippet(headline),
+            "&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt; the <mark>search</mark> &amp; &#39;more&#39;"
+        );
+    }
+
+    #[test]
+    fn markup_around_a_match_stays_escaped() {
+        assert_eq!(
+            render_snippet("<b>\u{2}<script>\u{3}</b>"),
+            "&lt;b&gt;<mark>&lt;script&gt;</mark>&lt;/b&gt;"
+        );
+    }
+}
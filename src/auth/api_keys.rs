@@ -0,0 +1,170 @@
+use actix_web::http::Method;
+use rand::RngCore;
+use sqlx::PgPool;
+
+use crate::auth::models::TokenClaims;
+use crate::auth::tokens::hash_token;
+
+pub const API_KEY_PREFIX: &str = "ak_";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ApiScope {
+    ArticlesRead,
+    ArticlesWrite,
+    AccountRead,
+    AccountWrite,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 4] = [
+        ApiScope::ArticlesRead,
+        ApiScope::ArticlesWrite,
+        ApiScope::AccountRead,
+        ApiScope::AccountWrite,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::ArticlesRead => "articles:read",
+            ApiScope::ArticlesWrite => "articles:write",
+            ApiScope::AccountRead => "account:read",
+            ApiScope::AccountWrite => "account:write",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<ApiScope> {
+        ApiScope::ALL.into_iter().find(|known| known.as_str() == scope)
+    }
+
+    /// The scope a request needs, or `None` when API keys may not call it at all
+    /// (key management, sessions, 2FA, logout and admin endpoints need a real login).
+    fn required_for(method: &Method, path: &str) -> Option<ApiScope> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let read = method == Method::GET;
+
+        match segments.as_slice() {
+            ["article" | "articles", ..] if read => Some(ApiScope::ArticlesRead),
+            ["article", ..] => Some(ApiScope::ArticlesWrite),
+            ["user", _, "api-keys" | "sessions" | "2fa", ..] => None,
+            ["user", ..] if read => Some(ApiScope::AccountRead),
+            ["user", ..] => Some(ApiScope::AccountWrite),
+            _ => None,
+        }
+    }
+}
+
+/// Claims from a JWT carry no scopes and may call anything; API key claims
+/// are limited to the scopes granted when the key was created.
+pub fn scope_allows(claims: &TokenClaims, method: &Method, path: &str) -> bool {
+    match &claims.scopes {
+        None => true,
+        Some(scopes) => ApiScope::required_for(method, path)
+            .is_some_and(|required| scopes.iter().any(|scope| scope == required.as_str())),
+    }
+}
+
+/// Returns `(prefix, full key)`. The full key is only ever shown to its owner once.
+pub fn generate_api_key() -> (String, String) {
+    let mut prefix = [0u8; 4];
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut prefix);
+    rand::thread_rng().fill_bytes(&mut secret);
+
+    let prefix = hex::encode(prefix);
+    let key = format!("{}{}_{}", API_KEY_PREFIX, prefix, hex::encode(secret));
+    (prefix, key)
+}
+
+/// Looks up an active key and turns it into claims for its owner, recording
+/// the time it was used.
+pub async fn authenticate_api_key(db: &PgPool, key: &str) -> Result<Option<TokenClaims>, sqlx::Error> {
+    let prefix = match key
+        .strip_prefix(API_KEY_PREFIX)
+        .and_then(|rest| rest.split_once('_'))
+    {
+        Some((prefix, _)) => prefix,
+        None => return Ok(None),
+    };
+
+    let row = sqlx::query_as::<_, (i32, i32, String, Vec<String>, i64, Option<i64>)>(
+        "UPDATE api_keys SET last_used_at = NOW()
+        FROM users
+        WHERE api_keys.prefix = $1
+          AND api_keys.key_hash = $2
+          AND api_keys.revoked_at IS NULL
+          AND (api_keys.expires_at IS NULL OR api_keys.expires_at > NOW())
+          AND users.id = api_keys.user_id
+          AND (users.status = 'active' OR (users.status = 'suspended' AND users.suspended_until <= NOW()))
+        RETURNING api_keys.id, users.id, users.role, api_keys.scopes,
+                  EXTRACT(EPOCH FROM api_keys.created_at)::BIGINT,
+                  EXTRACT(EPOCH FROM api_keys.expires_at)::BIGINT",
+    )
+    .bind(prefix)
+    .bind(hash_token(key))
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|(key_id, user_id, role, scopes, created_at, expires_at)| TokenClaims {
+        id: user_id,
+        role,
+        iat: created_at,
+        nbf: created_at,
+        exp: expires_at.unwrap_or(i64::MAX),
+        jti: format!("api-key:{}", key_id),
+        generation: 0,
+        sid: None,
+        impersonator: None,
+        scopes: Some(scopes),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::Method, test::TestRequest};
+
+    use super::scope_allows;
+    use crate::auth::auth::routed_path;
+    use crate::auth::models::TokenClaims;
+
+    fn allows(scopes: &[&str], method: Method, uri: &str) -> bool {
+        let claims = TokenClaims {
+            id: 5,
+            role: "user".to_string(),
+            iat: 0,
+            nbf: 0,
+            exp: 0,
+            jti: "api-key:1".to_string(),
+            generation: 0,
+            sid: None,
+            impersonator: None,
+            scopes: Some(scopes.iter().map(|scope| scope.to_string()).collect()),
+        };
+        let req = TestRequest::default().method(method.clone()).uri(uri).to_srv_request();
+        scope_allows(&claims, &method, routed_path(&req))
+    }
+
+    #[test]
+    fn scopes_decide_what_a_key_may_call() {
+        assert!(allows(&["articles:read"], Method::GET, "/articles"));
+        assert!(!allows(&["articles:read"], Method::POST, "/article"));
+        assert!(allows(&["account:write"], Method::PUT, "/user/5/email"));
+        assert!(!allows(&["account:read"], Method::PUT, "/user/5/email"));
+    }
+
+    #[test]
+    fn keys_never_reach_key_management_sessions_or_2fa() {
+        let all = ["articles:read", "articles:write", "account:read", "account:write"];
+        assert!(!allows(&all, Method::POST, "/user/5/api-keys"));
+        assert!(!allows(&all, Method::GET, "/user/5/sessions"));
+        assert!(!allows(&all, Method::POST, "/user/5/2fa/setup"));
+    }
+
+    #[test]
+    fn percent_encoded_segments_are_decided_like_the_router() {
+        let all = ["articles:read", "articles:write", "account:read", "account:write"];
+        assert!(!allows(&all, Method::POST, "/user/5/%61pi-keys"));
+        assert!(!allows(&all, Method::POST, "/user/5/%32fa/setup"));
+        assert!(!allows(&all, Method::DELETE, "/user/5/%73essions/7"));
+        assert!(!allows(&["articles:read"], Method::POST, "/%61rticle"));
+    }
+}
@@ -1,14 +1,23 @@
-use actix_web::{dev::ServiceRequest, error::Error, HttpMessage};
+use actix_web::{
+    dev::ServiceRequest,
+    error::Error,
+    get, post,
+    web::{Data, Json, ReqData},
+    HttpMessage, HttpRequest, HttpResponse, Responder,
+};
 use actix_web_httpauth::extractors::{
     bearer::{self, BearerAuth},
     AuthenticationError,
 };
-use hmac::{Hmac, Mac};
-use jwt::VerifyWithKey;
-
-use sha2::Sha256;
 
-use crate::auth::models::TokenClaims; 
+use crate::auth::api_keys::{authenticate_api_key, scope_allows, API_KEY_PREFIX};
+use crate::auth::impersonation::{impersonation_allows, record_impersonated_request};
+use crate::auth::models::{AppState, LogoutBody, RefreshTokenBody, TokenClaims};
+use crate::auth::revocation::{is_revoked, revoke_all_tokens, revoke_token};
+use crate::auth::sessions::{revoke_session, touch_session, ClientInfo};
+use crate::auth::tokens::{hash_token, issue_session, issue_token_pair};
+use crate::error::AppError;
+use crate::users::account_status::account_block;
 
 
 
@@ -17,27 +26,188 @@ pub async fn validator(
     req: ServiceRequest,
     credentials: BearerAuth,
 ) -> Result<ServiceRequest, (Error, ServiceRequest)> {
-    let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set!");
-    let key: Hmac<Sha256> = Hmac::new_from_slice(jwt_secret.as_bytes()).unwrap();
+    let state = req
+        .app_data::<Data<AppState>>()
+        .expect("AppState must be registered")
+        .clone();
     let token_string = credentials.token();
 
-    let claims: Result<TokenClaims, _> = token_string
-        .verify_with_key(&key)
-        .map_err(|_| "Invalid token");
+    if token_string.starts_with(API_KEY_PREFIX) {
+        return match authenticate_api_key(&state.db, token_string).await {
+            Ok(Some(claims)) if scope_allows(&claims, req.method(), routed_path(&req)) => {
+                req.extensions_mut().insert(claims);
+                Ok(req)
+            }
+            Ok(Some(_)) => Err((AppError::forbidden("API key scopes do not allow this request").into(), req)),
+            Ok(None) => Err(unauthorized(req)),
+            Err(error) => Err((AppError::from(error).into(), req)),
+        };
+    }
+
+    let claims = state.keys.verify::<TokenClaims>(token_string);
+
+    let claims = match claims {
+        Ok(claims) => claims,
+        Err(_) => return Err(unauthorized(req)),
+    };
 
-    match claims {
-        Ok(value) => {
-            req.extensions_mut().insert(value);
-            Ok(req)
+    match is_revoked(&state.db, &claims).await {
+        Ok(false) => {}
+        Ok(true) => return Err(unauthorized(req)),
+        Err(error) => return Err((AppError::from(error).into(), req)),
+    }
+
+    // Suspending or banning an account locks out tokens it already holds.
+    match account_block(&state.db, claims.id).await {
+        Ok(None) => {}
+        Ok(Some(_)) => return Err(unauthorized(req)),
+        Err(error) => return Err((AppError::from(error).into(), req)),
+    }
+
+    if let Some(session_id) = claims.sid {
+        match touch_session(&state.db, session_id).await {
+            Ok(true) => {}
+            Ok(false) => return Err(unauthorized(req)),
+            Err(error) => return Err((AppError::from(error).into(), req)),
         }
-        Err(_) => {
-            let config = req
-                .app_data::<bearer::Config>()
-                .cloned()
-                .unwrap_or_default()
-                .scope("");
-
-            Err((AuthenticationError::from(config).into(), req))
+    }
+
+    if let Some(admin_id) = claims.impersonator {
+        let path = routed_path(&req);
+        let allowed = impersonation_allows(req.method(), path);
+
+        if let Err(error) =
+            record_impersonated_request(&state.db, &claims, admin_id, req.method(), path, allowed).await
+        {
+            return Err((AppError::from(error).into(), req));
+        }
+
+        if !allowed {
+            return Err((AppError::forbidden("Not allowed while impersonating").into(), req));
+        }
+    }
+
+    req.extensions_mut().insert(claims);
+    Ok(req)
+}
+
+/// The path as the router sees it, percent-decoded except for `%`, `/` and `+`.
+/// Checks on the raw path would let `/user/5/em%61il` past a rule for `email`.
+pub fn routed_path(req: &ServiceRequest) -> &str {
+    req.match_info().get_ref().path()
+}
+
+fn unauthorized(req: ServiceRequest) -> (Error, ServiceRequest) {
+    let config = req
+        .app_data::<bearer::Config>()
+        .cloned()
+        .unwrap_or_default()
+        .scope("");
+
+    (AuthenticationError::from(config).into(), req)
+}
+
+#[post("/token/refresh")]
+async fn refresh_access_token(
+    state: Data<AppState>,
+    req: HttpRequest,
+    body: Json<RefreshTokenBody>,
+) -> Result<HttpResponse, AppError> {
+    let token_hash = hash_token(&body.into_inner().refresh_token);
+
+    // Rotate: the presented token is consumed whether or not the rest succeeds.
+    let rotated = sqlx::query_as::<_, (i32, String, Option<i32>)>(
+        "UPDATE refresh_tokens SET revoked_at = NOW()
+        FROM users
+        WHERE refresh_tokens.token_hash = $1
+          AND refresh_tokens.revoked_at IS NULL
+          AND refresh_tokens.expires_at > NOW()
+          AND users.id = refresh_tokens.user_id
+        RETURNING users.id, users.role, refresh_tokens.session_id",
+    )
+    .bind(&token_hash)
+    .fetch_optional(&state.db)
+    .await?;
+
+    let (user_id, role, session_id) = match rotated {
+        Some(rotated) => rotated,
+        None => {
+            // A rotated token being replayed means it leaked somewhere, so
+            // every refresh token that user still holds is burned. Tokens of
+            // a session that was ended on purpose are just rejected.
+            sqlx::query(
+                "UPDATE refresh_tokens SET revoked_at = NOW()
+                WHERE revoked_at IS NULL
+                  AND user_id = (SELECT refresh_tokens.user_id FROM refresh_tokens
+                                 LEFT JOIN sessions ON sessions.id = refresh_tokens.session_id
+                                 WHERE refresh_tokens.token_hash = $1
+                                   AND refresh_tokens.revoked_at IS NOT NULL
+                                   AND sessions.revoked_at IS NULL)",
+            )
+            .bind(&token_hash)
+            .execute(&state.db)
+            .await?;
+
+            return Err(AppError::unauthorized("Invalid or expired refresh token"));
         }
+    };
+
+    // Same rule as for access tokens: a suspended or banned account gets no new ones.
+    if let Some(status) = account_block(&state.db, user_id).await? {
+        return Err(AppError::AccountBlocked(status));
     }
-}
\ No newline at end of file
+
+    let tokens = match session_id {
+        Some(session_id) => issue_token_pair(&state, user_id, role, session_id).await?,
+        // Refresh tokens from before sessions existed get one on their first rotation.
+        None => issue_session(&state, user_id, role, &ClientInfo::from_request(&req)).await?,
+    };
+
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+#[post("/logout")]
+async fn logout(
+    state: Data<AppState>,
+    req_user: Option<ReqData<TokenClaims>>,
+    body: Option<Json<LogoutBody>>,
+) -> Result<HttpResponse, AppError> {
+    let user = req_user.ok_or_else(|| AppError::unauthorized("Unable to verify identity"))?;
+
+    revoke_token(&state.db, &user).await?;
+
+    if let Some(session_id) = user.sid {
+        revoke_session(&state.db, user.id, session_id).await?;
+    }
+
+    if let Some(refresh_token) = body.and_then(|body| body.into_inner().refresh_token) {
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = NOW()
+            WHERE token_hash = $1 AND user_id = $2 AND revoked_at IS NULL",
+        )
+        .bind(hash_token(&refresh_token))
+        .bind(user.id)
+        .execute(&state.db)
+        .await?;
+    }
+
+    Ok(HttpResponse::Ok().json("Logged out"))
+}
+
+#[post("/logout/all")]
+async fn logout_all(
+    state: Data<AppState>,
+    req_user: Option<ReqData<TokenClaims>>,
+) -> Result<HttpResponse, AppError> {
+    let user = req_user.ok_or_else(|| AppError::unauthorized("Unable to verify identity"))?;
+    user.require_not_impersonating()?;
+
+    revoke_all_tokens(&state, user.id).await?;
+
+    Ok(HttpResponse::Ok().json("Logged out from all devices"))
+}
+
+#[get("/.well-known/jwks.json")]
+async fn jwks(state: Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(state.keys.jwks())
+}
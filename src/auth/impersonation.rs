@@ -0,0 +1,165 @@
+use actix_web::{
+    http::Method,
+    post,
+    web::{Data, Path},
+    HttpResponse,
+};
+use chrono::Utc;
+use rand::RngCore;
+use sqlx::PgPool;
+
+use crate::auth::models::{AppState, ImpersonationResponse, TokenClaims};
+use crate::auth::permissions::{role_permissions, Authorized, Permission};
+use crate::error::AppError;
+
+/// Whether a request may be made while impersonating. Anything that would
+/// change how the user signs in, or act with admin rights, stays off limits.
+pub fn impersonation_allows(method: &Method, path: &str) -> bool {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    if method == Method::GET {
+        return !matches!(segments.as_slice(), ["admin", ..]);
+    }
+
+    !matches!(
+        segments.as_slice(),
+        ["user", _, "password" | "email" | "username" | "role" | "api-keys" | "sessions" | "2fa", ..]
+            | ["user", _]
+            | ["verify-email", ..]
+            | ["logout", "all"]
+            | ["admin", ..]
+            | ["roles", ..]
+    )
+}
+
+pub async fn record_impersonated_request(
+    db: &PgPool,
+    claims: &TokenClaims,
+    admin_id: i32,
+    method: &Method,
+    path: &str,
+    allowed: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO impersonation_audit_log (admin_id, user_id, jti, method, path, allowed)
+        VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(admin_id)
+    .bind(claims.id)
+    .bind(&claims.jti)
+    .bind(method.as_str())
+    .bind(path)
+    .bind(allowed)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+#[post("/admin/impersonate/{user_id}")]
+async fn impersonate(
+    state: Data<AppState>,
+    auth: Authorized,
+    user_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+
+    auth.require(Permission::UserImpersonate)?;
+
+    if auth.claims.impersonator.is_some() {
+        return Err(AppError::forbidden("Stop impersonating before impersonating someone else"));
+    }
+
+    if auth.id() == user_id {
+        return Err(AppError::bad_request("You cannot impersonate yourself"));
+    }
+
+    let (role, generation) = sqlx::query_as::<_, (String, i64)>("SELECT role, token_generation FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::not_found("User not found"))?;
+
+    // Acting as another impersonator would hide who really did what.
+    if role_permissions(&state.db, &role).await?.contains(&Permission::UserImpersonate) {
+        return Err(AppError::forbidden("Users who can impersonate cannot be impersonated"));
+    }
+
+    let mut jti = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut jti);
+
+    let now = Utc::now().timestamp();
+    let expires_in = state.config.tokens.impersonation_ttl_seconds;
+    let claims = TokenClaims {
+        id: user_id,
+        role,
+        iat: now,
+        nbf: now,
+        exp: now + expires_in,
+        jti: format!("impersonation:{}", hex::encode(jti)),
+        generation,
+        sid: None,
+        impersonator: Some(auth.id()),
+        scopes: None,
+    };
+
+    record_impersonated_request(
+        &state.db,
+        &claims,
+        auth.id(),
+        &Method::POST,
+        &format!("/admin/impersonate/{}", user_id),
+        true,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ImpersonationResponse {
+        access_token: state.keys.sign(&claims),
+        expires_in,
+        user_id,
+        impersonator_id: auth.id(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::Method, test::TestRequest};
+
+    use super::impersonation_allows;
+    use crate::auth::auth::routed_path;
+
+    fn allows(method: Method, uri: &str) -> bool {
+        let req = TestRequest::default().method(method.clone()).uri(uri).to_srv_request();
+        impersonation_allows(&method, routed_path(&req))
+    }
+
+    #[test]
+    fn allows_reading_and_articles() {
+        assert!(allows(Method::GET, "/user/5"));
+        assert!(allows(Method::GET, "/user/5/sessions"));
+        assert!(allows(Method::POST, "/article"));
+        assert!(allows(Method::PUT, "/article/3/title"));
+    }
+
+    #[test]
+    fn denies_account_and_admin_changes() {
+        assert!(!allows(Method::PUT, "/user/5/email"));
+        assert!(!allows(Method::PUT, "/user/5/password"));
+        assert!(!allows(Method::POST, "/user/5/api-keys"));
+        assert!(!allows(Method::POST, "/user/5/2fa/setup"));
+        assert!(!allows(Method::DELETE, "/user/5/sessions/7"));
+        assert!(!allows(Method::DELETE, "/user/5"));
+        assert!(!allows(Method::POST, "/logout/all"));
+        assert!(!allows(Method::GET, "/admin/users"));
+    }
+
+    #[test]
+    fn denies_percent_encoded_segments() {
+        assert!(!allows(Method::PUT, "/user/5/em%61il"));
+        assert!(!allows(Method::POST, "/user/5/%61pi-keys"));
+        assert!(!allows(Method::POST, "/user/5/%32fa/confirm"));
+        assert!(!allows(Method::DELETE, "/user/5/%73essions/7"));
+        assert!(!allows(Method::GET, "/%61dmin/users"));
+        assert!(!allows(Method::POST, "/%6Cogout/all"));
+    }
+}
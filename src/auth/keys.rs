@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPublicKey;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+
+use crate::config::JwtConfig;
+
+const HMAC_KEY_ID: &str = "hs256";
+
+struct VerificationKey {
+    algorithm: Algorithm,
+    decoding: DecodingKey,
+    // Symmetric keys are never published, so they have no JWK.
+    jwk: Option<Jwk>,
+}
+
+/// Signs tokens with the active key and verifies them with any key that is
+/// still trusted, picked by the `kid` header. Keeping the previous key in the
+/// verification set during a rotation lets already issued tokens run out.
+pub struct KeyStore {
+    kid: String,
+    algorithm: Algorithm,
+    encoding: EncodingKey,
+    verification: HashMap<String, VerificationKey>,
+}
+
+fn parse_algorithm(algorithm: &str) -> Result<Algorithm, String> {
+    match algorithm {
+        "RS256" => Ok(Algorithm::RS256),
+        "EdDSA" => Ok(Algorithm::EdDSA),
+        other => Err(format!("Unsupported JWT algorithm {}, use RS256 or EdDSA", other)),
+    }
+}
+
+fn read_key_file(path: &Path) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|error| format!("Cannot read key file {}: {}", path.display(), error))
+}
+
+/// Builds the decoding key and public JWK for a PEM encoded public key.
+fn verification_key(kid: &str, algorithm: Algorithm, public_pem: &[u8]) -> Result<VerificationKey, String> {
+    let pem_string = String::from_utf8_lossy(public_pem);
+
+    let (decoding, jwk) = match algorithm {
+        Algorithm::RS256 => {
+            let public_key = RsaPublicKey::from_public_key_pem(&pem_string)
+                .or_else(|_| RsaPublicKey::from_pkcs1_pem(&pem_string))
+                .map_err(|error| format!("Invalid RSA public key for {}: {}", kid, error))?;
+
+            (
+                DecodingKey::from_rsa_pem(public_pem)
+                    .map_err(|error| format!("Invalid RSA public key for {}: {}", kid, error))?,
+                json!({
+                    "kty": "RSA",
+                    "use": "sig",
+                    "alg": "RS256",
+                    "kid": kid,
+                    "n": URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be()),
+                    "e": URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be()),
+                }),
+            )
+        }
+        Algorithm::EdDSA => {
+            let der = pem::parse(public_pem)
+                .map_err(|error| format!("Invalid Ed25519 public key for {}: {}", kid, error))?;
+            if der.tag() != "PUBLIC KEY" || der.contents().len() < 32 {
+                return Err(format!("Invalid Ed25519 public key for {}", kid));
+            }
+            // An Ed25519 SubjectPublicKeyInfo ends with the raw 32 byte key.
+            let raw_key = &der.contents()[der.contents().len() - 32..];
+
+            (
+                DecodingKey::from_ed_pem(public_pem)
+                    .map_err(|error| format!("Invalid Ed25519 public key for {}: {}", kid, error))?,
+                json!({
+                    "kty": "OKP",
+                    "use": "sig",
+                    "alg": "EdDSA",
+                    "crv": "Ed25519",
+                    "kid": kid,
+                    "x": URL_SAFE_NO_PAD.encode(raw_key),
+                }),
+            )
+        }
+        other => return Err(format!("Unsupported JWT algorithm {:?} for {}", other, kid)),
+    };
+
+    Ok(VerificationKey {
+        algorithm,
+        decoding,
+        jwk: Some(serde_json::from_value(jwk).map_err(|error| format!("Error building the JWK for {}: {}", kid, error))?),
+    })
+}
+
+/// Signs a throwaway token and checks it against the public key, so a public
+/// key file that belongs to another key pair is caught before any token is issued.
+fn check_key_pair(algorithm: Algorithm, encoding: &EncodingKey, public: &VerificationKey) -> Result<(), String> {
+    let token = encode(&Header::new(algorithm), &json!({ "check": true }), encoding)
+        .map_err(|error| format!("Cannot sign with the JWT signing key: {}", error))?;
+
+    let mut validation = Validation::new(algorithm);
+    validation.required_spec_claims.clear();
+    validation.validate_exp = false;
+
+    decode::<serde_json::Value>(&token, &public.decoding, &validation)
+        .map(|_| ())
+        .map_err(|_| "jwt.signing_public_key_file does not belong to jwt.signing_key_file".to_string())
+}
+
+impl KeyStore {
+    /// With `signing_key_file` set, tokens are signed with that private key
+    /// (`signing_algorithm`, `signing_key_id`, `signing_public_key_file`).
+    /// Extra keys that are still accepted go in `verification_keys` as
+    /// `kid:ALGORITHM:public.pem` entries. Without a key file it falls back to
+    /// HS256 with `secret`. Fails on missing settings, key files that can't be
+    /// read or parsed, and a public key that doesn't match the signing key.
+    pub fn from_config(config: &JwtConfig) -> Result<Self, String> {
+        let mut verification = HashMap::new();
+
+        for entry in &config.verification_keys {
+            let mut parts = entry.splitn(3, ':');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(kid), Some(algorithm), Some(path)) => {
+                    let key = verification_key(kid, parse_algorithm(algorithm)?, &read_key_file(Path::new(path))?)?;
+                    verification.insert(kid.to_string(), key);
+                }
+                _ => return Err("jwt.verification_keys entries must look like kid:ALGORITHM:path".to_string()),
+            }
+        }
+
+        let (kid, algorithm, encoding) = match &config.signing_key_file {
+            Some(path) => {
+                let kid = config
+                    .signing_key_id
+                    .clone()
+                    .ok_or_else(|| "jwt.signing_key_id must be set with a signing key file".to_string())?;
+                let algorithm = parse_algorithm(&config.signing_algorithm)?;
+                let private_pem = read_key_file(path)?;
+                let encoding = match algorithm {
+                    Algorithm::RS256 => EncodingKey::from_rsa_pem(&private_pem),
+                    _ => EncodingKey::from_ed_pem(&private_pem),
+                }
+                .map_err(|error| format!("Invalid JWT signing key {}: {}", path.display(), error))?;
+
+                let public_path = config
+                    .signing_public_key_file
+                    .as_deref()
+                    .ok_or_else(|| "jwt.signing_public_key_file must be set with a signing key file".to_string())?;
+                let public = verification_key(&kid, algorithm, &read_key_file(public_path)?)?;
+                check_key_pair(algorithm, &encoding, &public)?;
+                verification.insert(kid.clone(), public);
+
+                (kid, algorithm, encoding)
+            }
+            None => {
+                let secret = config
+                    .secret
+                    .as_deref()
+                    .ok_or_else(|| "jwt.secret must be set without a signing key file".to_string())?;
+                verification.insert(
+                    HMAC_KEY_ID.to_string(),
+                    VerificationKey {
+                        algorithm: Algorithm::HS256,
+                        decoding: DecodingKey::from_secret(secret.as_bytes()),
+                        jwk: None,
+                    },
+                );
+
+                (HMAC_KEY_ID.to_string(), Algorithm::HS256, EncodingKey::from_secret(secret.as_bytes()))
+            }
+        };
+
+        Ok(KeyStore {
+            kid,
+            algorithm,
+            encoding,
+            verification,
+        })
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> String {
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.kid.clone());
+
+        encode(&header, claims, &self.encoding).expect("Error signing token")
+    }
+
+    /// Checks the signature plus `exp` and `nbf`.
+    pub fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<T, jsonwebtoken::errors::Error> {
+        let header = decode_header(token)?;
+        let key = header
+            .kid
+            .as_deref()
+            .and_then(|kid| self.verification.get(kid))
+            .filter(|key| key.algorithm == header.alg)
+            .ok_or(jsonwebtoken::errors::ErrorKind::InvalidToken)?;
+
+        let mut validation = Validation::new(key.algorithm);
+        validation.validate_nbf = true;
+        validation.leeway = 0;
+
+        decode::<T>(token, &key.decoding, &validation).map(|data| data.claims)
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .verification
+                .values()
+                .filter_map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+/// Failures allowed before an account or address is locked.
+const ACCOUNT_THRESHOLD: i32 = 5;
+const IP_THRESHOLD: i32 = 20;
+/// The first lockout lasts this long and doubles with every further failure.
+const BASE_LOCKOUT_SECONDS: i64 = 30;
+const MAX_LOCKOUT_SECONDS: i64 = 60 * 60;
+
+pub fn account_key(username: &str) -> String {
+    format!("user:{}", username.to_lowercase())
+}
+
+pub fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+fn lockout_duration(failures: i32, threshold: i32) -> Duration {
+    let doublings = (failures - threshold).clamp(0, 20) as u32;
+    Duration::seconds((BASE_LOCKOUT_SECONDS << doublings).min(MAX_LOCKOUT_SECONDS))
+}
+
+/// Seconds until the longest running lockout on any of the keys ends.
+pub async fn locked_for(db: &PgPool, keys: &[String]) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT CEIL(EXTRACT(EPOCH FROM MAX(locked_until) - NOW()))::BIGINT
+        FROM login_failures
+        WHERE key = ANY($1) AND locked_until > NOW()",
+    )
+    .bind(keys)
+    .fetch_one(db)
+    .await
+}
+
+async fn record_failure_for(db: &PgPool, key: &str, threshold: i32) -> Result<(), sqlx::Error> {
+    // Counts start over once an hour passed without failures.
+    let failures = sqlx::query_scalar::<_, i32>(
+        "INSERT INTO login_failures (key, failures, last_failure_at)
+        VALUES ($1, 1, NOW())
+        ON CONFLICT (key) DO UPDATE SET
+            failures = CASE
+                WHEN login_failures.last_failure_at < NOW() - INTERVAL '1 hour' THEN 1
+                ELSE login_failures.failures + 1
+            END,
+            last_failure_at = NOW()
+        RETURNING failures",
+    )
+    .bind(key)
+    .fetch_one(db)
+    .await?;
+
+    if failures >= threshold {
+        sqlx::query("UPDATE login_failures SET locked_until = $1 WHERE key = $2")
+            .bind(Utc::now() + lockout_duration(failures, threshold))
+            .bind(key)
+            .execute(db)
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn record_failure(db: &PgPool, username: &str, ip: &str) -> Result<(), sqlx::Error> {
+    record_failure_for(db, &account_key(username), ACCOUNT_THRESHOLD).await?;
+    record_failure_for(db, &ip_key(ip), IP_THRESHOLD).await
+}
+
+/// A successful login clears the account's count, the address keeps its own.
+pub async fn record_success(db: &PgPool, username: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM login_failures WHERE key = $1")
+        .bind(account_key(username))
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn purge_stale(db: &PgPool) -> Result<u64, sqlx::Error> {
+    sqlx::query(
+        "DELETE FROM login_failures
+        WHERE last_failure_at < NOW() - INTERVAL '1 hour'
+          AND (locked_until IS NULL OR locked_until < NOW())",
+    )
+    .execute(db)
+    .await
+    .map(|result| result.rows_affected())
+}
@@ -1,6 +1,23 @@
+pub mod api_keys;
+#[allow(clippy::module_inception)]
 pub mod auth;
+pub mod impersonation;
+pub mod keys;
+pub mod lockout;
 pub mod models;
+pub mod oidc;
+pub mod password;
+pub mod permissions;
+pub mod revocation;
+pub mod sessions;
+pub mod tokens;
+pub mod totp;
 
 
-pub use auth::validator;
+pub use auth::{jwks, logout, logout_all, refresh_access_token, validator};
+pub use impersonation::impersonate;
+pub use keys::KeyStore;
 pub use models::{AppState,TokenClaims,};
+pub use oidc::{oidc_callback, oidc_login, OidcProviders};
+pub use password::PasswordHasher;
+pub use revocation::RevocationPurger;
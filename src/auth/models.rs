@@ -1,15 +1,47 @@
 use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Postgres};
 use std::fmt;
+use std::sync::Arc;
+
+use crate::auth::keys::KeyStore;
+use crate::auth::oidc::OidcProviders;
+use crate::auth::password::PasswordHasher;
+use crate::config::Config;
+use crate::error::AppError;
+use crate::mail::Mailer;
+use crate::users::PasswordPolicy;
 
 pub struct AppState {
     pub db: Pool<Postgres>,
+    pub mailer: Arc<dyn Mailer>,
+    pub oidc: Arc<OidcProviders>,
+    pub keys: Arc<KeyStore>,
+    pub hasher: Arc<PasswordHasher>,
+    pub password_policy: Arc<PasswordPolicy>,
+    pub config: Arc<Config>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TokenClaims {
     pub id: i32,
     pub role: String,
+    pub iat: i64,
+    pub nbf: i64,
+    pub exp: i64,
+    pub jti: String,
+    /// The user's `token_generation` when the token was issued, revoking all of
+    /// a user's tokens moves it on. Missing on tokens older than the counter.
+    #[serde(default)]
+    pub generation: i64,
+    /// The login session the token belongs to, absent for API keys.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<i32>,
+    /// The admin acting as `id`, only set on impersonation tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonator: Option<i32>,
+    /// Only set when the request was made with an API key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
 }
 
 impl fmt::Debug for TokenClaims {
@@ -17,6 +49,67 @@ impl fmt::Debug for TokenClaims {
         f.debug_struct("TokenClaims")
             .field("id", &self.id)
             .field("role", &self.role)
+            .field("iat", &self.iat)
+            .field("nbf", &self.nbf)
+            .field("exp", &self.exp)
+            .field("jti", &self.jti)
+            .field("generation", &self.generation)
+            .field("sid", &self.sid)
+            .field("impersonator", &self.impersonator)
+            .field("scopes", &self.scopes)
             .finish()
     }
-}
\ No newline at end of file
+}
+
+impl TokenClaims {
+    /// For anything that changes how the user signs in or who they are.
+    pub fn require_not_impersonating(&self) -> Result<(), AppError> {
+        match self.impersonator {
+            Some(_) => Err(AppError::forbidden("Not allowed while impersonating")),
+            None => Ok(()),
+        }
+    }
+
+    /// Key management, sessions and 2FA need a real login.
+    pub fn require_not_api_key(&self) -> Result<(), AppError> {
+        match self.scopes {
+            Some(_) => Err(AppError::forbidden("API keys cannot be used for this request")),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Always "Bearer".
+    pub token_type: &'static str,
+    pub expires_in: i64,
+}
+
+#[derive(Serialize)]
+pub struct ImpersonationResponse {
+    pub access_token: String,
+    pub expires_in: i64,
+    pub user_id: i32,
+    pub impersonator_id: i32,
+}
+
+#[derive(Deserialize)]
+pub struct RefreshTokenBody {
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct LogoutBody {
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
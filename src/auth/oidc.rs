@@ -0,0 +1,653 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration as StdDuration, Instant};
+
+use actix_web::{
+    get,
+    http::header,
+    web::{Data, Path, Query},
+    HttpRequest, HttpResponse,
+};
+use chrono::{Duration, Utc};
+use openidconnect::core::{CoreAuthenticationFlow, CoreClient, CoreProviderMetadata};
+use openidconnect::reqwest::async_http_client;
+use openidconnect::{
+    AccessTokenHash, AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl, Nonce,
+    OAuth2TokenResponse, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope,
+    TokenResponse as OidcTokenResponse,
+};
+
+use crate::auth::models::{AppState, OidcCallbackQuery};
+use crate::auth::tokens::{generate_opaque_token, hash_token};
+use crate::config::Config;
+use crate::error::AppError;
+use crate::users::account_deletion::is_reserved_username;
+use crate::users::account_status::account_block;
+use crate::users::models::UserNoPassword;
+use crate::users::users::start_login;
+
+const LOGIN_STATE_TTL_MINUTES: i64 = 10;
+const DISCOVERY_CACHE_TTL: StdDuration = StdDuration::from_secs(60 * 60);
+const DEFAULT_SCOPES: [&str; 3] = ["openid", "email", "profile"];
+
+#[derive(Clone)]
+pub struct OidcProviderConfig {
+    pub name: String,
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+    pub scopes: Vec<String>,
+}
+
+/// Configured identity providers plus their discovered clients. Discovery and
+/// the JWKS are fetched lazily and cached for an hour.
+pub struct OidcProviders {
+    providers: HashMap<String, OidcProviderConfig>,
+    clients: RwLock<HashMap<String, (Instant, CoreClient)>>,
+}
+
+impl OidcProviders {
+    pub fn new(providers: Vec<OidcProviderConfig>) -> Self {
+        OidcProviders {
+            providers: providers
+                .into_iter()
+                .map(|provider| (provider.name.clone(), provider))
+                .collect(),
+            clients: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Builds the providers from the `[oidc.<name>]` settings, filling in the
+    /// default redirect URL and scopes.
+    pub fn from_config(config: &Config) -> Self {
+        let providers = config
+            .oidc
+            .iter()
+            .map(|(name, settings)| OidcProviderConfig {
+                name: name.clone(),
+                issuer_url: settings.issuer_url.clone(),
+                client_id: settings.client_id.clone(),
+                client_secret: settings.client_secret.clone(),
+                redirect_url: settings.redirect_url.clone().unwrap_or_else(|| {
+                    format!("{}/auth/oidc/{}/callback", config.server.base_url, name)
+                }),
+                scopes: if settings.scopes.is_empty() {
+                    DEFAULT_SCOPES.iter().map(|scope| scope.to_string()).collect()
+                } else {
+                    settings.scopes.clone()
+                },
+            })
+            .collect();
+
+        OidcProviders::new(providers)
+    }
+
+    pub fn provider(&self, name: &str) -> Option<&OidcProviderConfig> {
+        self.providers.get(name)
+    }
+
+    async fn client(&self, provider: &OidcProviderConfig) -> Result<CoreClient, String> {
+        if let Some((fetched_at, client)) = self.clients.read().unwrap().get(&provider.name) {
+            if fetched_at.elapsed() < DISCOVERY_CACHE_TTL {
+                return Ok(client.clone());
+            }
+        }
+
+        let issuer_url = IssuerUrl::new(provider.issuer_url.clone()).map_err(|error| format!("{:?}", error))?;
+        let redirect_url = RedirectUrl::new(provider.redirect_url.clone()).map_err(|error| format!("{:?}", error))?;
+
+        let metadata = CoreProviderMetadata::discover_async(issuer_url, async_http_client)
+            .await
+            .map_err(|error| format!("{:?}", error))?;
+
+        let client = CoreClient::from_provider_metadata(
+            metadata,
+            ClientId::new(provider.client_id.clone()),
+            provider.client_secret.clone().map(ClientSecret::new),
+        )
+        .set_redirect_uri(redirect_url);
+
+        self.clients
+            .write()
+            .unwrap()
+            .insert(provider.name.clone(), (Instant::now(), client.clone()));
+
+        Ok(client)
+    }
+}
+
+struct ExternalIdentity {
+    subject: String,
+    email: Option<String>,
+    email_verified: bool,
+    preferred_username: Option<String>,
+}
+
+#[get("/auth/oidc/{provider}/login")]
+async fn oidc_login(state: Data<AppState>, provider: Path<String>) -> Result<HttpResponse, AppError> {
+    let provider = state
+        .oidc
+        .provider(&provider.into_inner())
+        .cloned()
+        .ok_or_else(|| AppError::not_found("Unknown identity provider"))?;
+
+    let client = state
+        .oidc
+        .client(&provider)
+        .await
+        .map_err(|error| AppError::bad_gateway(format!("Identity provider discovery failed: {}", error)))?;
+
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+    let mut request = client
+        .authorize_url(
+            CoreAuthenticationFlow::AuthorizationCode,
+            CsrfToken::new_random,
+            Nonce::new_random,
+        )
+        .set_pkce_challenge(pkce_challenge);
+
+    for scope in provider.scopes.iter().filter(|scope| scope.as_str() != "openid") {
+        request = request.add_scope(Scope::new(scope.clone()));
+    }
+
+    let (authorize_url, csrf_token, nonce) = request.url();
+
+    sqlx::query(
+        "INSERT INTO oidc_login_states (state_hash, provider, pkce_verifier, nonce, expires_at)
+        VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(hash_token(csrf_token.secret()))
+    .bind(&provider.name)
+    .bind(pkce_verifier.secret())
+    .bind(nonce.secret())
+    .bind(Utc::now() + Duration::minutes(LOGIN_STATE_TTL_MINUTES))
+    .execute(&state.db)
+    .await?;
+
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, authorize_url.to_string()))
+        .finish())
+}
+
+#[get("/auth/oidc/{provider}/callback")]
+async fn oidc_callback(
+    state: Data<AppState>,
+    req: HttpRequest,
+    provider: Path<String>,
+    query: Query<OidcCallbackQuery>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+
+    let provider = state
+        .oidc
+        .provider(&provider.into_inner())
+        .cloned()
+        .ok_or_else(|| AppError::not_found("Unknown identity provider"))?;
+
+    if let Some(error) = query.error {
+        return Err(AppError::unauthorized(format!(
+            "Identity provider returned an error: {} {}",
+            error,
+            query.error_description.unwrap_or_default()
+        )));
+    }
+
+    let (code, csrf_state) = match (query.code, query.state) {
+        (Some(code), Some(csrf_state)) => (code, csrf_state),
+        _ => return Err(AppError::bad_request("Missing code or state")),
+    };
+
+    let (pkce_verifier, nonce) = sqlx::query_as::<_, (String, String)>(
+        "DELETE FROM oidc_login_states
+        WHERE state_hash = $1 AND provider = $2 AND expires_at > NOW()
+        RETURNING pkce_verifier, nonce",
+    )
+    .bind(hash_token(&csrf_state))
+    .bind(&provider.name)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::bad_request("Invalid or expired login state"))?;
+
+    let client = state
+        .oidc
+        .client(&provider)
+        .await
+        .map_err(|error| AppError::bad_gateway(format!("Identity provider discovery failed: {}", error)))?;
+
+    let identity = exchange_code(&client, code, pkce_verifier, nonce)
+        .await
+        .map_err(|error| AppError::unauthorized(format!("Identity provider login failed: {}", error)))?;
+
+    let user_id = find_or_create_user(&state, &provider.name, &identity).await?;
+
+    if let Some(status) = account_block(&state.db, user_id).await? {
+        return Err(AppError::AccountBlocked(status));
+    }
+
+    let user = sqlx::query_as::<_, UserNoPassword>("SELECT id, username, email, role FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&state.db)
+        .await?;
+
+    start_login(&state, &req, user).await
+}
+
+async fn exchange_code(
+    client: &CoreClient,
+    code: String,
+    pkce_verifier: String,
+    nonce: String,
+) -> Result<ExternalIdentity, String> {
+    let token_response = client
+        .exchange_code(AuthorizationCode::new(code))
+        .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier))
+        .request_async(async_http_client)
+        .await
+        .map_err(|error| format!("{:?}", error))?;
+
+    let id_token = token_response
+        .id_token()
+        .ok_or_else(|| "No ID token in the token response".to_string())?;
+
+    let claims = id_token
+        .claims(&client.id_token_verifier(), &Nonce::new(nonce))
+        .map_err(|error| format!("{:?}", error))?;
+
+    if let Some(expected_hash) = claims.access_token_hash() {
+        let algorithm = id_token.signing_alg().map_err(|error| format!("{:?}", error))?;
+        let actual_hash = AccessTokenHash::from_token(token_response.access_token(), &algorithm)
+            .map_err(|error| format!("{:?}", error))?;
+
+        if actual_hash != *expected_hash {
+            return Err("Access token hash does not match the ID token".to_string());
+        }
+    }
+
+    Ok(ExternalIdentity {
+        subject: claims.subject().to_string(),
+        email: claims.email().map(|email| email.to_string()),
+        email_verified: claims.email_verified().unwrap_or(false),
+        preferred_username: claims.preferred_username().map(|username| username.to_string()),
+    })
+}
+
+/// Returns the user linked to the external identity. Unknown identities are
+/// linked to the local account with the same verified email, or get a new
+/// account without a usable password.
+async fn find_or_create_user(
+    state: &AppState,
+    provider: &str,
+    identity: &ExternalIdentity,
+) -> Result<i32, AppError> {
+    let linked = sqlx::query_scalar::<_, i32>(
+        "SELECT users.id FROM user_identities
+        JOIN users ON users.id = user_identities.user_id
+        WHERE user_identities.provider = $1 AND user_identities.subject = $2",
+    )
+    .bind(provider)
+    .bind(&identity.subject)
+    .fetch_optional(&state.db)
+    .await?;
+
+    if let Some(user_id) = linked {
+        return Ok(user_id);
+    }
+
+    let email = identity
+        .email
+        .clone()
+        .ok_or_else(|| AppError::bad_request("The identity provider did not share an email address"))?;
+
+    let existing = sqlx::query_as::<_, (i32, bool)>(
+        "SELECT id, verified_at IS NOT NULL FROM users WHERE email = $1",
+    )
+    .bind(&email)
+    .fetch_optional(&state.db)
+    .await?;
+
+    let user_id = match existing {
+        Some((id, true)) if identity.email_verified => id,
+        Some(_) => {
+            return Err(AppError::conflict(
+                "An account with this email already exists, log in with your password instead",
+            ))
+        }
+        None => create_user(state, identity, &email).await?,
+    };
+
+    sqlx::query(
+        "INSERT INTO user_identities (user_id, provider, subject, email) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(user_id)
+    .bind(provider)
+    .bind(&identity.subject)
+    .bind(&email)
+    .execute(&state.db)
+    .await?;
+
+    Ok(user_id)
+}
+
+async fn create_user(
+    state: &AppState,
+    identity: &ExternalIdentity,
+    email: &str,
+) -> Result<i32, AppError> {
+    let base_username = identity
+        .preferred_username
+        .clone()
+        .unwrap_or_else(|| email.split('@').next().unwrap_or("user").to_string());
+    // Nobody knows this password, the account can only sign in through the provider
+    // until its owner sets one with the password reset flow.
+    let hashed_password = state.hasher.hash_async(&generate_opaque_token()).await?;
+
+    for attempt in 0..5 {
+        let username = if attempt == 0 && !is_reserved_username(&base_username) {
+            base_username.clone()
+        } else {
+            format!("{}-{}", base_username, &generate_opaque_token()[..6])
+        };
+
+        match sqlx::query_scalar::<_, i32>(
+            "INSERT INTO users (username, password, email, role, verified_at)
+            VALUES ($1, $2, $3, 'user', CASE WHEN $4 THEN NOW() END)
+            RETURNING id",
+        )
+        .bind(&username)
+        .bind(&hashed_password)
+        .bind(email)
+        .bind(identity.email_verified)
+        .fetch_one(&state.db)
+        .await
+        {
+            Ok(user_id) => return Ok(user_id),
+            // Only a taken username is worth another try, a taken email stays taken.
+            Err(sqlx::Error::Database(error)) if error.constraint() == Some("users_username_key") => continue,
+            Err(sqlx::Error::Database(error)) if error.constraint() == Some("users_email_key") => {
+                return Err(AppError::conflict(
+                    "An account with this email already exists, log in with your password instead",
+                ))
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    Err(AppError::conflict("Could not find a free username for this account"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    use actix_web::{
+        dev::ServiceResponse,
+        test::{self, TestRequest},
+        web::{self, Data, Form},
+        App, HttpResponse, HttpServer,
+    };
+    use argon2::Params;
+    use chrono::Utc;
+    use openidconnect::url::Url;
+    use rsa::pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding};
+    use rsa::RsaPrivateKey;
+    use serde_json::{json, Value};
+    use sqlx::postgres::PgPoolOptions;
+    use sqlx::PgPool;
+
+    use super::{oidc_callback, oidc_login, OidcProviderConfig, OidcProviders};
+    use crate::auth::models::AppState;
+    use crate::auth::tokens::generate_opaque_token;
+    use crate::auth::{KeyStore, PasswordHasher};
+    use crate::config::{Config, JwtConfig, PasswordConfig};
+    use crate::mail::mailer_from_config;
+    use crate::migrate::MIGRATOR;
+    use crate::users::PasswordPolicy;
+
+    const CLIENT_ID: &str = "articles-test";
+    const LOGIN_URI: &str = "/auth/oidc/mock/login";
+
+    /// A local identity provider: discovery, JWKS and a token endpoint that
+    /// answers for the codes a test handed out beforehand.
+    struct MockIssuer {
+        url: String,
+        keys: KeyStore,
+        /// ID token claims by authorization code.
+        codes: Mutex<HashMap<String, Value>>,
+    }
+
+    async fn discovery(issuer: Data<MockIssuer>) -> HttpResponse {
+        HttpResponse::Ok().json(json!({
+            "issuer": issuer.url,
+            "authorization_endpoint": format!("{}/authorize", issuer.url),
+            "token_endpoint": format!("{}/token", issuer.url),
+            "jwks_uri": format!("{}/jwks", issuer.url),
+            "response_types_supported": ["code"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["RS256"],
+        }))
+    }
+
+    async fn jwks(issuer: Data<MockIssuer>) -> HttpResponse {
+        HttpResponse::Ok().json(issuer.keys.jwks())
+    }
+
+    async fn token(issuer: Data<MockIssuer>, form: Form<HashMap<String, String>>) -> HttpResponse {
+        let claims = form
+            .get("code")
+            .and_then(|code| issuer.codes.lock().unwrap().remove(code));
+
+        match claims {
+            Some(claims) => HttpResponse::Ok().json(json!({
+                "access_token": generate_opaque_token(),
+                "token_type": "Bearer",
+                "expires_in": 300,
+                "id_token": issuer.keys.sign(&claims),
+            })),
+            None => HttpResponse::BadRequest().json(json!({ "error": "invalid_grant" })),
+        }
+    }
+
+    /// Serves the mock issuer on a free local port, signing with a fresh RSA key.
+    fn start_issuer() -> Data<MockIssuer> {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap();
+        let dir = std::env::temp_dir().join(format!("mock-issuer-{}", &generate_opaque_token()[..12]));
+        std::fs::create_dir_all(&dir).unwrap();
+        let private_path = dir.join("private.pem");
+        let public_path = dir.join("public.pem");
+        std::fs::write(&private_path, private_key.to_pkcs8_pem(LineEnding::LF).unwrap().as_bytes()).unwrap();
+        std::fs::write(&public_path, private_key.to_public_key().to_public_key_pem(LineEnding::LF).unwrap()).unwrap();
+
+        let keys = KeyStore::from_config(&JwtConfig {
+            signing_key_file: Some(private_path),
+            signing_public_key_file: Some(public_path),
+            signing_key_id: Some("mock".to_string()),
+            ..JwtConfig::default()
+        })
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let issuer = Data::new(MockIssuer {
+            url: format!("http://{}", listener.local_addr().unwrap()),
+            keys,
+            codes: Mutex::new(HashMap::new()),
+        });
+
+        let app_issuer = issuer.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_issuer.clone())
+                .route("/.well-known/openid-configuration", web::get().to(discovery))
+                .route("/jwks", web::get().to(jwks))
+                .route("/token", web::post().to(token))
+        })
+        .workers(1)
+        .disable_signals()
+        .listen(listener)
+        .unwrap()
+        .run();
+        actix_web::rt::spawn(server);
+
+        issuer
+    }
+
+    /// The migrated Postgres in `DATABASE_URL`.
+    async fn test_db() -> PgPool {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL must point at a Postgres to test against");
+        let db = PgPoolOptions::new().max_connections(2).connect(&url).await.unwrap();
+        MIGRATOR.run(&db).await.unwrap();
+        db
+    }
+
+    /// The app's state with the mock issuer as the "mock" provider.
+    fn app_state(db: PgPool, issuer: &MockIssuer) -> AppState {
+        let config = Config::default();
+        AppState {
+            db,
+            mailer: mailer_from_config(&config.mail),
+            oidc: Arc::new(OidcProviders::new(vec![OidcProviderConfig {
+                name: "mock".to_string(),
+                issuer_url: issuer.url.clone(),
+                client_id: CLIENT_ID.to_string(),
+                client_secret: None,
+                redirect_url: "http://localhost:8080/auth/oidc/mock/callback".to_string(),
+                scopes: vec!["openid".to_string(), "email".to_string(), "profile".to_string()],
+            }])),
+            keys: Arc::new(KeyStore::from_config(&JwtConfig {
+                secret: Some("test-secret".to_string()),
+                ..JwtConfig::default()
+            })
+            .unwrap()),
+            hasher: Arc::new(PasswordHasher::new(Params::new(1024, 1, 1, None).unwrap(), None)),
+            password_policy: Arc::new(PasswordPolicy::from_config(&PasswordConfig::default())),
+            config: Arc::new(config),
+        }
+    }
+
+    /// Lets the mock issuer vouch for `identity` on the login `oidc_login` started
+    /// and returns the URI the browser would be sent back to.
+    fn callback_uri(issuer: &MockIssuer, login: &ServiceResponse, identity: Value) -> String {
+        assert_eq!(login.status(), 302);
+
+        let location = Url::parse(login.headers().get("location").unwrap().to_str().unwrap()).unwrap();
+        assert!(location.as_str().starts_with(&format!("{}/authorize?", issuer.url)));
+        let param = |name: &str| {
+            location
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+                .unwrap()
+        };
+
+        let mut claims = json!({
+            "iss": issuer.url,
+            "aud": CLIENT_ID,
+            "iat": Utc::now().timestamp(),
+            "exp": Utc::now().timestamp() + 300,
+            "nonce": param("nonce"),
+        });
+        claims.as_object_mut().unwrap().extend(identity.as_object().unwrap().clone());
+
+        let code = generate_opaque_token();
+        issuer.codes.lock().unwrap().insert(code.clone(), claims);
+
+        format!("/auth/oidc/mock/callback?code={}&state={}", code, urlencoding::encode(&param("state")))
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn creates_a_user_for_an_unknown_identity() {
+        let db = test_db().await;
+        let issuer = start_issuer();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(app_state(db.clone(), &issuer)))
+                .service(oidc_login)
+                .service(oidc_callback),
+        )
+        .await;
+
+        let suffix = &generate_opaque_token()[..10];
+        let subject = format!("new-{}", suffix);
+        let email = format!("new-{}@example.test", suffix);
+        let identity = json!({
+            "sub": subject,
+            "email": email,
+            "email_verified": true,
+            "preferred_username": format!("oidc-{}", suffix),
+        });
+        let login = test::call_service(&app, TestRequest::get().uri(LOGIN_URI).to_request()).await;
+        let uri = callback_uri(&issuer, &login, identity);
+        let response = test::call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 200);
+
+        let body: Value = test::read_body_json(response).await;
+        assert!(body["access_token"].is_string());
+        assert_eq!(body["user"]["username"], format!("oidc-{}", suffix));
+        assert_eq!(body["user"]["email"], email);
+        assert_eq!(body["user"]["role"], "user");
+
+        let linked = sqlx::query_scalar::<_, i32>(
+            "SELECT user_id FROM user_identities WHERE provider = 'mock' AND subject = $1",
+        )
+        .bind(&subject)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        assert_eq!(Value::from(linked), body["user"]["id"]);
+
+        sqlx::query("DELETE FROM users WHERE id = $1").bind(linked).execute(&db).await.unwrap();
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn links_an_existing_user_by_verified_email() {
+        let db = test_db().await;
+        let issuer = start_issuer();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(app_state(db.clone(), &issuer)))
+                .service(oidc_login)
+                .service(oidc_callback),
+        )
+        .await;
+
+        let suffix = &generate_opaque_token()[..10];
+        let email = format!("existing-{}@example.test", suffix);
+        let user_id = sqlx::query_scalar::<_, i32>(
+            "INSERT INTO users (username, password, email, role, verified_at)
+            VALUES ($1, 'unused', $2, 'user', NOW())
+            RETURNING id",
+        )
+        .bind(format!("existing-{}", suffix))
+        .bind(&email)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+
+        let subject = format!("existing-{}", suffix);
+        let login = test::call_service(&app, TestRequest::get().uri(LOGIN_URI).to_request()).await;
+        let uri = callback_uri(&issuer, &login, json!({ "sub": subject, "email": email, "email_verified": true }));
+        let response = test::call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 200);
+
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["user"]["id"], user_id);
+        assert_eq!(body["user"]["username"], format!("existing-{}", suffix));
+
+        // The second login finds the user through the link, not the email.
+        let identity = json!({ "sub": subject, "email": format!("changed-{}", email), "email_verified": true });
+        let login = test::call_service(&app, TestRequest::get().uri(LOGIN_URI).to_request()).await;
+        let uri = callback_uri(&issuer, &login, identity);
+        let response = test::call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), 200);
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["user"]["id"], user_id);
+
+        sqlx::query("DELETE FROM users WHERE id = $1").bind(user_id).execute(&db).await.unwrap();
+    }
+}
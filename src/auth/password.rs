@@ -0,0 +1,120 @@
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordVerifier, SaltString};
+use std::sync::Arc;
+
+use actix_web::web;
+use argon2::{Algorithm, Argon2, Params, PasswordHasher as _, Version};
+
+use crate::config::PasswordConfig;
+use crate::error::AppError;
+
+pub struct PasswordCheck {
+    pub valid: bool,
+    /// The stored hash uses bcrypt or older Argon2 parameters and should be
+    /// replaced now that the plain password is at hand.
+    pub needs_rehash: bool,
+}
+
+/// Hashes new passwords with Argon2id and still accepts the bcrypt hashes
+/// written before it. The optional pepper is mixed in as the Argon2 secret,
+/// so it never ends up in the database.
+pub struct PasswordHasher {
+    params: Params,
+    pepper: Option<Vec<u8>>,
+    dummy_hash: String,
+}
+
+impl PasswordHasher {
+    pub fn new(params: Params, pepper: Option<Vec<u8>>) -> Self {
+        let mut hasher = PasswordHasher {
+            params,
+            pepper,
+            dummy_hash: String::new(),
+        };
+        hasher.dummy_hash = hasher.hash("dummy-password");
+        hasher
+    }
+
+    /// Uses the Argon2 cost settings and the pepper from `[passwords]`.
+    pub fn from_config(config: &PasswordConfig) -> Self {
+        let params = Params::new(
+            config.argon2_memory_kib,
+            config.argon2_iterations,
+            config.argon2_parallelism,
+            None,
+        )
+        .expect("Invalid Argon2 parameters");
+
+        let pepper = config.hash_secret.clone().map(String::into_bytes);
+
+        PasswordHasher::new(params, pepper)
+    }
+
+    fn argon2(&self) -> Argon2<'_> {
+        match &self.pepper {
+            Some(pepper) => Argon2::new_with_secret(pepper, Algorithm::Argon2id, Version::V0x13, self.params.clone())
+                .expect("Invalid Argon2 pepper"),
+            None => Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone()),
+        }
+    }
+
+    pub fn hash(&self, password: &str) -> String {
+        let salt = SaltString::generate(&mut OsRng);
+
+        self.argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .expect("Error hashing password")
+            .to_string()
+    }
+
+    pub fn verify(&self, password: &str, stored_hash: &str) -> PasswordCheck {
+        if stored_hash.starts_with("$2") {
+            return PasswordCheck {
+                valid: bcrypt::verify(password, stored_hash).unwrap_or(false),
+                needs_rehash: true,
+            };
+        }
+
+        let parsed = match PasswordHash::new(stored_hash) {
+            Ok(parsed) => parsed,
+            Err(_) => {
+                return PasswordCheck {
+                    valid: false,
+                    needs_rehash: false,
+                }
+            }
+        };
+
+        let valid = self
+            .argon2()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok();
+
+        let needs_rehash = parsed.algorithm != Algorithm::Argon2id.ident()
+            || Params::try_from(&parsed).map_or(true, |params| {
+                params.m_cost() != self.params.m_cost()
+                    || params.t_cost() != self.params.t_cost()
+                    || params.p_cost() != self.params.p_cost()
+            });
+
+        PasswordCheck { valid, needs_rehash }
+    }
+
+    /// `hash` on the blocking thread pool, handlers use this so Argon2 doesn't
+    /// stall every other request on their worker.
+    pub async fn hash_async(self: &Arc<Self>, password: &str) -> Result<String, AppError> {
+        let (hasher, password) = (self.clone(), password.to_string());
+        Ok(web::block(move || hasher.hash(&password)).await?)
+    }
+
+    /// `verify` on the blocking thread pool.
+    pub async fn verify_async(self: &Arc<Self>, password: &str, stored_hash: &str) -> Result<PasswordCheck, AppError> {
+        let (hasher, password, stored_hash) = (self.clone(), password.to_string(), stored_hash.to_string());
+        Ok(web::block(move || hasher.verify(&password, &stored_hash)).await?)
+    }
+
+    /// Does the same work as a real check, for logins with an unknown username.
+    pub async fn verify_dummy(self: &Arc<Self>, password: &str) -> Result<(), AppError> {
+        let dummy_hash = self.dummy_hash.clone();
+        self.verify_async(password, &dummy_hash).await.map(|_| ())
+    }
+}
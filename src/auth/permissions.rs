@@ -0,0 +1,149 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use actix_web::{dev::Payload, web::Data, Error, FromRequest, HttpMessage, HttpRequest};
+use sqlx::PgPool;
+
+use crate::auth::models::{AppState, TokenClaims};
+use crate::error::AppError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Permission {
+    ArticleCreate,
+    ArticleUpdateAny,
+    ArticleDeleteAny,
+    ArticlePublish,
+    UserReadAny,
+    UserUpdateAny,
+    RoleManage,
+    MfaPolicyManage,
+    UserUnlock,
+    UserImpersonate,
+    UserSuspend,
+    UserDeleteAny,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 12] = [
+        Permission::ArticleCreate,
+        Permission::ArticleUpdateAny,
+        Permission::ArticleDeleteAny,
+        Permission::ArticlePublish,
+        Permission::UserReadAny,
+        Permission::UserUpdateAny,
+        Permission::RoleManage,
+        Permission::MfaPolicyManage,
+        Permission::UserUnlock,
+        Permission::UserImpersonate,
+        Permission::UserSuspend,
+        Permission::UserDeleteAny,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::ArticleCreate => "article:create",
+            Permission::ArticleUpdateAny => "article:update:any",
+            Permission::ArticleDeleteAny => "article:delete:any",
+            Permission::ArticlePublish => "article:publish",
+            Permission::UserReadAny => "user:read:any",
+            Permission::UserUpdateAny => "user:update:any",
+            Permission::RoleManage => "role:manage",
+            Permission::MfaPolicyManage => "mfa:policy:manage",
+            Permission::UserUnlock => "user:unlock",
+            Permission::UserImpersonate => "user:impersonate",
+            Permission::UserSuspend => "user:suspend",
+            Permission::UserDeleteAny => "user:delete:any",
+        }
+    }
+
+    pub fn parse(permission: &str) -> Option<Permission> {
+        Permission::ALL.into_iter().find(|known| known.as_str() == permission)
+    }
+}
+
+pub async fn role_permissions(db: &PgPool, role: &str) -> Result<Vec<Permission>, sqlx::Error> {
+    let permissions =
+        sqlx::query_scalar::<_, String>("SELECT permission FROM role_permissions WHERE role = $1")
+            .bind(role)
+            .fetch_all(db)
+            .await?;
+
+    Ok(permissions.iter().filter_map(|permission| Permission::parse(permission)).collect())
+}
+
+/// The authenticated caller together with the permissions of their role.
+/// Handlers take it as an argument and ask it before doing privileged work.
+pub struct Authorized {
+    pub claims: TokenClaims,
+    permissions: Vec<Permission>,
+}
+
+impl Authorized {
+    pub fn id(&self) -> i32 {
+        self.claims.id
+    }
+
+    pub fn permissions(&self) -> &[Permission] {
+        &self.permissions
+    }
+
+    pub fn can(&self, permission: Permission) -> bool {
+        self.permissions.contains(&permission)
+    }
+
+    /// Allowed when the caller owns the resource or holds `permission`.
+    pub fn owns_or_can(&self, owner_id: i32, permission: Permission) -> bool {
+        self.claims.id == owner_id || self.can(permission)
+    }
+
+    pub fn require(&self, permission: Permission) -> Result<(), AppError> {
+        if self.can(permission) {
+            Ok(())
+        } else {
+            Err(AppError::forbidden(format!("Missing permission {}", permission.as_str())))
+        }
+    }
+}
+
+#[cfg(test)]
+impl Authorized {
+    /// A caller holding `permissions`, for testing rules without a request.
+    pub fn with_permissions(id: i32, permissions: Vec<Permission>) -> Self {
+        Authorized {
+            claims: TokenClaims {
+                id,
+                role: "test".to_string(),
+                iat: 0,
+                nbf: 0,
+                exp: 0,
+                jti: String::new(),
+                generation: 0,
+                sid: None,
+                impersonator: None,
+                scopes: None,
+            },
+            permissions,
+        }
+    }
+}
+
+impl FromRequest for Authorized {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let claims = req.extensions().get::<TokenClaims>().cloned();
+        let state = req.app_data::<Data<AppState>>().cloned();
+
+        Box::pin(async move {
+            let claims = claims.ok_or_else(|| AppError::unauthorized("Unable to verify identity"))?;
+            let state = state.ok_or_else(|| AppError::internal("AppState must be registered"))?;
+
+            let permissions = role_permissions(&state.db, &claims.role)
+                .await
+                .map_err(AppError::from)?;
+
+            Ok(Authorized { claims, permissions })
+        })
+    }
+}
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use actix::{Actor, AsyncContext, Context};
+use sqlx::PgPool;
+
+use crate::auth::lockout::purge_stale;
+use crate::auth::models::{AppState, TokenClaims};
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Revoked on its own by `jti`, or along with everything the user held when
+/// their token generation moved past the one it was issued under.
+pub async fn is_revoked(db: &PgPool, claims: &TokenClaims) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM token_revocations WHERE jti = $1)
+            OR EXISTS (SELECT 1 FROM users WHERE id = $2 AND token_generation > $3)",
+    )
+    .bind(&claims.jti)
+    .bind(claims.id)
+    .bind(claims.generation)
+    .fetch_one(db)
+    .await
+}
+
+/// Revokes the single access token described by `claims`.
+pub async fn revoke_token(db: &PgPool, claims: &TokenClaims) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO token_revocations (jti, user_id, expires_at)
+        VALUES ($1, $2, to_timestamp($3))
+        ON CONFLICT (jti) DO NOTHING",
+    )
+    .bind(&claims.jti)
+    .bind(claims.id)
+    .bind(claims.exp as f64)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Revokes every access token issued to the user so far, tokens issued from
+/// now on carry the new generation. Refresh tokens keep working, so clients
+/// pick up changes such as a new role on their next refresh.
+pub async fn revoke_access_tokens(state: &AppState, user_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET token_generation = token_generation + 1 WHERE id = $1")
+        .bind(user_id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(())
+}
+
+/// Revokes every access and refresh token issued to the user so far, ending all their sessions.
+pub async fn revoke_all_tokens(state: &AppState, user_id: i32) -> Result<(), sqlx::Error> {
+    let db = &state.db;
+    revoke_access_tokens(state, user_id).await?;
+
+    sqlx::query("UPDATE sessions SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    sqlx::query("UPDATE refresh_tokens SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Revokes the user's API keys, they were minted with the old password and
+/// have to be made again after it changed.
+pub async fn revoke_api_keys(db: &PgPool, user_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE api_keys SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn purge_expired(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let revocations = sqlx::query("DELETE FROM token_revocations WHERE expires_at < NOW()")
+        .execute(db)
+        .await?;
+
+    let refresh_tokens = sqlx::query("DELETE FROM refresh_tokens WHERE expires_at < NOW()")
+        .execute(db)
+        .await?;
+
+    // Refresh tokens go first, a session never expires before its newest one.
+    let sessions = sqlx::query("DELETE FROM sessions WHERE expires_at < NOW()")
+        .execute(db)
+        .await?;
+
+    let reset_tokens = sqlx::query("DELETE FROM password_reset_tokens WHERE expires_at < NOW()")
+        .execute(db)
+        .await?;
+
+    let mfa_challenges = sqlx::query("DELETE FROM mfa_challenges WHERE expires_at < NOW()")
+        .execute(db)
+        .await?;
+
+    let oidc_states = sqlx::query("DELETE FROM oidc_login_states WHERE expires_at < NOW()")
+        .execute(db)
+        .await?;
+
+    let login_failures = purge_stale(db).await?;
+
+    Ok(revocations.rows_affected()
+        + refresh_tokens.rows_affected()
+        + sessions.rows_affected()
+        + reset_tokens.rows_affected()
+        + mfa_challenges.rows_affected()
+        + oidc_states.rows_affected()
+        + login_failures)
+}
+
+/// Periodically drops revocation entries and stored tokens that have expired on their own.
+pub struct RevocationPurger {
+    pub db: PgPool,
+}
+
+impl Actor for RevocationPurger {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(PURGE_INTERVAL, |purger, _| {
+            let db = purger.db.clone();
+            actix::spawn(async move {
+                if let Err(error) = purge_expired(&db).await {
+                    eprintln!("Failed to purge expired tokens: {:?}", error);
+                }
+            });
+        });
+    }
+}
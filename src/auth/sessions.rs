@@ -0,0 +1,155 @@
+use actix_web::{http::header, HttpRequest};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// Where a login came from, recorded on the session it starts.
+pub struct ClientInfo {
+    pub user_agent: String,
+    pub ip: String,
+}
+
+impl ClientInfo {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        ClientInfo {
+            user_agent: req
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string(),
+            ip: req
+                .peer_addr()
+                .map(|addr| addr.ip().to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+pub async fn start_session(
+    db: &PgPool,
+    user_id: i32,
+    client: &ClientInfo,
+    expires_at: DateTime<Utc>,
+) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>(
+        "INSERT INTO sessions (user_id, device, user_agent, ip, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id",
+    )
+    .bind(user_id)
+    .bind(describe_device(&client.user_agent))
+    .bind(&client.user_agent)
+    .bind(&client.ip)
+    .bind(expires_at)
+    .fetch_one(db)
+    .await
+}
+
+/// Whether the session is still live, bumping its last-seen time at most once a minute.
+pub async fn touch_session(db: &PgPool, session_id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "WITH touched AS (
+            UPDATE sessions SET last_seen_at = NOW()
+            WHERE id = $1 AND revoked_at IS NULL AND last_seen_at < NOW() - INTERVAL '1 minute'
+        )
+        SELECT EXISTS (
+            SELECT 1 FROM sessions WHERE id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        )",
+    )
+    .bind(session_id)
+    .fetch_one(db)
+    .await
+}
+
+/// Ends one session along with its refresh tokens. Returns `false` when the
+/// user has no such session.
+pub async fn revoke_session(db: &PgPool, user_id: i32, session_id: i32) -> Result<bool, sqlx::Error> {
+    let revoked = sqlx::query(
+        "UPDATE sessions SET revoked_at = COALESCE(revoked_at, NOW())
+        WHERE id = $1 AND user_id = $2",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .execute(db)
+    .await?;
+
+    if revoked.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    revoke_refresh_tokens(db, session_id).await?;
+
+    Ok(true)
+}
+
+/// Revokes the refresh tokens of a session but keeps the session itself, so it
+/// can carry on with a pair issued afterwards.
+pub async fn revoke_refresh_tokens(db: &PgPool, session_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE refresh_tokens SET revoked_at = NOW() WHERE session_id = $1 AND revoked_at IS NULL")
+        .bind(session_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Ends every session of the user except `keep`, together with their refresh tokens.
+pub async fn revoke_other_sessions(db: &PgPool, user_id: i32, keep: Option<i32>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE sessions SET revoked_at = NOW()
+        WHERE user_id = $1 AND revoked_at IS NULL AND id IS DISTINCT FROM $2",
+    )
+    .bind(user_id)
+    .bind(keep)
+    .execute(db)
+    .await?;
+
+    sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = NOW()
+        WHERE user_id = $1 AND revoked_at IS NULL AND session_id IS DISTINCT FROM $2",
+    )
+    .bind(user_id)
+    .bind(keep)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// A short "Browser on OS" label so a session list is readable at a glance,
+/// the full user-agent is kept next to it.
+fn describe_device(user_agent: &str) -> String {
+    if user_agent.is_empty() {
+        return "Unknown device".to_string();
+    }
+
+    let browser = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+        ("curl/", "curl"),
+    ]
+    .iter()
+    .find(|(marker, _)| user_agent.contains(marker))
+    .map(|(_, name)| *name);
+
+    let os = [
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iPadOS"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("Linux", "Linux"),
+    ]
+    .iter()
+    .find(|(marker, _)| user_agent.contains(marker))
+    .map(|(_, name)| *name);
+
+    match (browser, os) {
+        (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+        (Some(name), None) | (None, Some(name)) => name.to_string(),
+        (None, None) => user_agent.split('/').next().unwrap_or(user_agent).to_string(),
+    }
+}
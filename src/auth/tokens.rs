@@ -0,0 +1,107 @@
+use chrono::{Duration, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::auth::keys::KeyStore;
+use crate::auth::models::{AppState, TokenClaims, TokenResponse};
+use crate::auth::sessions::{start_session, ClientInfo};
+
+fn refresh_token_ttl(state: &AppState) -> Duration {
+    Duration::days(state.config.tokens.refresh_ttl_days)
+}
+
+pub fn sign_access_token(
+    keys: &KeyStore,
+    ttl_seconds: i64,
+    user_id: i32,
+    role: String,
+    generation: i64,
+    session_id: i32,
+) -> String {
+    let now = Utc::now().timestamp();
+    let claims = TokenClaims {
+        id: user_id,
+        role,
+        iat: now,
+        nbf: now,
+        exp: now + ttl_seconds,
+        jti: generate_token_id(),
+        generation,
+        sid: Some(session_id),
+        impersonator: None,
+        scopes: None,
+    };
+
+    keys.sign(&claims)
+}
+
+fn generate_token_id() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Returns a random, URL-safe opaque token. Only its hash is ever stored.
+pub fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Starts a session for a fresh login and issues its first token pair.
+pub async fn issue_session(
+    state: &AppState,
+    user_id: i32,
+    role: String,
+    client: &ClientInfo,
+) -> Result<TokenResponse, sqlx::Error> {
+    let session_id = start_session(&state.db, user_id, client, Utc::now() + refresh_token_ttl(state)).await?;
+
+    issue_token_pair(state, user_id, role, session_id).await
+}
+
+/// Signs a new access token and stores a fresh refresh token for the session,
+/// which stays alive as long as its newest refresh token.
+pub async fn issue_token_pair(
+    state: &AppState,
+    user_id: i32,
+    role: String,
+    session_id: i32,
+) -> Result<TokenResponse, sqlx::Error> {
+    let access_ttl = state.config.tokens.access_ttl_seconds;
+    let refresh_token = generate_opaque_token();
+    let expires_at = Utc::now() + refresh_token_ttl(state);
+
+    sqlx::query(
+        "INSERT INTO refresh_tokens (user_id, token_hash, expires_at, session_id)
+        VALUES ($1, $2, $3, $4)",
+    )
+    .bind(user_id)
+    .bind(hash_token(&refresh_token))
+    .bind(expires_at)
+    .bind(session_id)
+    .execute(&state.db)
+    .await?;
+
+    sqlx::query("UPDATE sessions SET expires_at = $1, last_seen_at = NOW() WHERE id = $2")
+        .bind(expires_at)
+        .bind(session_id)
+        .execute(&state.db)
+        .await?;
+
+    let generation = sqlx::query_scalar::<_, i64>("SELECT token_generation FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&state.db)
+        .await?;
+
+    Ok(TokenResponse {
+        access_token: sign_access_token(&state.keys, access_ttl, user_id, role, generation, session_id),
+        refresh_token,
+        token_type: "Bearer",
+        expires_in: access_ttl,
+    })
+}
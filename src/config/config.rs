@@ -0,0 +1,756 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use argon2::Params;
+use clap::Parser;
+use serde::Deserialize;
+
+use crate::auth::KeyStore;
+
+/// Command line flags, they win over the config file and the environment.
+#[derive(Parser)]
+#[command(about = "Article API with accounts, roles and token auth")]
+pub struct Cli {
+    /// TOML file to read settings from, `CONFIG_FILE` by default.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+    /// Address to listen on, e.g. 0.0.0.0:8080.
+    #[arg(long)]
+    pub bind: Option<String>,
+    /// Number of worker threads, one per CPU core by default.
+    #[arg(long)]
+    pub workers: Option<usize>,
+    /// Postgres connection string.
+    #[arg(long)]
+    pub database_url: Option<String>,
+    /// Size of the database connection pool.
+    #[arg(long)]
+    pub max_connections: Option<u32>,
+    /// Apply the database migrations and exit.
+    #[arg(long, conflicts_with = "check_migrations")]
+    pub migrate_only: bool,
+    /// Report pending or changed migrations without applying them, exits
+    /// with an error unless the schema is up to date.
+    #[arg(long)]
+    pub check_migrations: bool,
+}
+
+impl Cli {
+    /// Only the database settings matter when just the schema is handled.
+    pub fn migrations_only(&self) -> bool {
+        self.migrate_only || self.check_migrations
+    }
+}
+
+/// Every setting the app reads. Built once at startup from the defaults below,
+/// then the optional TOML file, then environment variables, then [`Cli`] flags,
+/// each layer overriding the one before it.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub tokens: TokenConfig,
+    pub jwt: JwtConfig,
+    pub passwords: PasswordConfig,
+    pub admin: AdminConfig,
+    pub mail: MailConfig,
+    pub two_factor: TwoFactorConfig,
+    pub search: SearchConfig,
+    /// Identity providers by name, `[oidc.google]` in the file.
+    pub oidc: BTreeMap<String, OidcProviderSettings>,
+    pub features: FeatureConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_address: String,
+    pub workers: Option<usize>,
+    /// Public URL used in links sent by mail and as the default OIDC redirect.
+    pub base_url: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_address: "127.0.0.1:8080".to_string(),
+            workers: None,
+            base_url: "http://localhost:8080".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_seconds: u64,
+    pub idle_timeout_seconds: Option<u64>,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: String::new(),
+            max_connections: 5,
+            min_connections: 0,
+            acquire_timeout_seconds: 30,
+            idle_timeout_seconds: Some(600),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenConfig {
+    pub access_ttl_seconds: i64,
+    pub refresh_ttl_days: i64,
+    pub impersonation_ttl_seconds: i64,
+    pub password_reset_ttl_minutes: i64,
+}
+
+impl Default for TokenConfig {
+    fn default() -> Self {
+        TokenConfig {
+            access_ttl_seconds: 900,
+            refresh_ttl_days: 30,
+            impersonation_ttl_seconds: 600,
+            password_reset_ttl_minutes: 60,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtConfig {
+    /// HS256 secret, only used without a signing key file.
+    pub secret: Option<String>,
+    pub signing_key_file: Option<PathBuf>,
+    pub signing_public_key_file: Option<PathBuf>,
+    pub signing_key_id: Option<String>,
+    pub signing_algorithm: String,
+    /// Keys still accepted during a rotation, as `kid:ALGORITHM:path`.
+    pub verification_keys: Vec<String>,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        JwtConfig {
+            secret: None,
+            signing_key_file: None,
+            signing_public_key_file: None,
+            signing_key_id: None,
+            signing_algorithm: "RS256".to_string(),
+            verification_keys: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordConfig {
+    /// Pepper mixed into every Argon2id hash.
+    pub hash_secret: Option<String>,
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    pub min_length: usize,
+    pub min_entropy_bits: f64,
+    pub breached_passwords_dir: Option<PathBuf>,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        // OWASP's recommended Argon2id settings.
+        PasswordConfig {
+            hash_secret: None,
+            argon2_memory_kib: 19 * 1024,
+            argon2_iterations: 2,
+            argon2_parallelism: 1,
+            min_length: 10,
+            min_entropy_bits: 45.0,
+            breached_passwords_dir: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    pub username: String,
+    pub password: String,
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailConfig {
+    /// `file` writes every mail to `outbox_dir`, `smtp` sends it through `smtp_host`.
+    pub transport: String,
+    pub from: String,
+    pub outbox_dir: PathBuf,
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        MailConfig {
+            transport: "file".to_string(),
+            from: "no-reply@localhost".to_string(),
+            outbox_dir: PathBuf::from("outbox"),
+            smtp_host: None,
+            smtp_port: 465,
+            smtp_username: None,
+            smtp_password: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TwoFactorConfig {
+    /// Name authenticator apps show next to the account.
+    pub issuer: String,
+}
+
+impl Default for TwoFactorConfig {
+    fn default() -> Self {
+        TwoFactorConfig {
+            issuer: "Rust Article App".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    /// Postgres text search configuration used for stemming and stop words, `english`,
+    /// `german`, `simple`... Changing it rebuilds the search index at the next start.
+    pub language: String,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            language: "english".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct OidcProviderSettings {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    /// Defaults to `<base_url>/auth/oidc/<name>/callback`.
+    pub redirect_url: Option<String>,
+    /// Defaults to `openid email profile`.
+    pub scopes: Vec<String>,
+}
+
+/// Parts of the API that can be switched off without a rebuild.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureConfig {
+    /// `POST /register`.
+    pub registration: bool,
+    /// The deprecated `GET /login` with Basic auth.
+    pub legacy_login: bool,
+    /// `POST /admin/impersonate/{user_id}`.
+    pub impersonation: bool,
+}
+
+impl Default for FeatureConfig {
+    fn default() -> Self {
+        FeatureConfig {
+            registration: true,
+            legacy_login: true,
+            impersonation: true,
+        }
+    }
+}
+
+/// Everything wrong with the configuration, reported together so it can be
+/// fixed in one go.
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Invalid configuration:")?;
+        for problem in &self.0 {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sets `target` from the variable when it is present and parses.
+fn env_value<T: FromStr>(key: &str, target: &mut T, problems: &mut Vec<String>) {
+    if let Ok(value) = std::env::var(key) {
+        match value.parse() {
+            Ok(value) => *target = value,
+            Err(_) => problems.push(format!("{} has an invalid value {:?}", key, value)),
+        }
+    }
+}
+
+/// Like [`env_value`], an empty variable clears the setting.
+fn env_optional<T: FromStr>(key: &str, target: &mut Option<T>, problems: &mut Vec<String>) {
+    if let Ok(value) = std::env::var(key) {
+        if value.is_empty() {
+            *target = None;
+        } else {
+            match value.parse() {
+                Ok(value) => *target = Some(value),
+                Err(_) => problems.push(format!("{} has an invalid value {:?}", key, value)),
+            }
+        }
+    }
+}
+
+fn env_list(key: &str, separator: char, target: &mut Vec<String>) {
+    if let Ok(value) = std::env::var(key) {
+        *target = value
+            .split(separator)
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+}
+
+impl Config {
+    /// Loads the configuration and checks it, nothing else in the app reads
+    /// the environment.
+    pub fn load(cli: &Cli) -> Result<Config, ConfigError> {
+        let mut problems = Vec::new();
+
+        let path = cli
+            .config
+            .clone()
+            .or_else(|| std::env::var("CONFIG_FILE").ok().filter(|path| !path.is_empty()).map(PathBuf::from));
+
+        let mut config = match path {
+            Some(path) => match fs::read_to_string(&path) {
+                Ok(contents) => toml::from_str(&contents).map_err(|error| {
+                    ConfigError(vec![format!("{}: {}", path.display(), error.message())])
+                })?,
+                Err(error) => return Err(ConfigError(vec![format!("Cannot read {}: {}", path.display(), error)])),
+            },
+            None => Config::default(),
+        };
+
+        config.apply_env(&mut problems);
+        config.apply_cli(cli);
+        config.validate_database(&mut problems);
+        if !cli.migrations_only() {
+            config.validate(&mut problems);
+        }
+
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError(problems))
+        }
+    }
+
+    fn apply_env(&mut self, problems: &mut Vec<String>) {
+        let server = &mut self.server;
+        env_value("BIND_ADDRESS", &mut server.bind_address, problems);
+        env_optional("WORKERS", &mut server.workers, problems);
+        env_value("APP_BASE_URL", &mut server.base_url, problems);
+
+        let database = &mut self.database;
+        env_value("DATABASE_URL", &mut database.url, problems);
+        env_value("DATABASE_MAX_CONNECTIONS", &mut database.max_connections, problems);
+        env_value("DATABASE_MIN_CONNECTIONS", &mut database.min_connections, problems);
+        env_value("DATABASE_ACQUIRE_TIMEOUT_SECONDS", &mut database.acquire_timeout_seconds, problems);
+        env_optional("DATABASE_IDLE_TIMEOUT_SECONDS", &mut database.idle_timeout_seconds, problems);
+
+        let tokens = &mut self.tokens;
+        env_value("ACCESS_TOKEN_TTL_SECONDS", &mut tokens.access_ttl_seconds, problems);
+        env_value("REFRESH_TOKEN_TTL_DAYS", &mut tokens.refresh_ttl_days, problems);
+        env_value("IMPERSONATION_TOKEN_TTL_SECONDS", &mut tokens.impersonation_ttl_seconds, problems);
+        env_value("PASSWORD_RESET_TTL_MINUTES", &mut tokens.password_reset_ttl_minutes, problems);
+
+        let jwt = &mut self.jwt;
+        env_optional("JWT_SECRET", &mut jwt.secret, problems);
+        env_optional("JWT_SIGNING_KEY_FILE", &mut jwt.signing_key_file, problems);
+        env_optional("JWT_SIGNING_PUBLIC_KEY_FILE", &mut jwt.signing_public_key_file, problems);
+        env_optional("JWT_SIGNING_KEY_ID", &mut jwt.signing_key_id, problems);
+        env_value("JWT_SIGNING_ALGORITHM", &mut jwt.signing_algorithm, problems);
+        env_list("JWT_VERIFICATION_KEYS", ',', &mut jwt.verification_keys);
+
+        let passwords = &mut self.passwords;
+        env_optional("HASH_SECRET", &mut passwords.hash_secret, problems);
+        env_value("ARGON2_MEMORY_KIB", &mut passwords.argon2_memory_kib, problems);
+        env_value("ARGON2_ITERATIONS", &mut passwords.argon2_iterations, problems);
+        env_value("ARGON2_PARALLELISM", &mut passwords.argon2_parallelism, problems);
+        env_value("PASSWORD_MIN_LENGTH", &mut passwords.min_length, problems);
+        env_value("PASSWORD_MIN_ENTROPY_BITS", &mut passwords.min_entropy_bits, problems);
+        env_optional("BREACHED_PASSWORDS_DIR", &mut passwords.breached_passwords_dir, problems);
+
+        let admin = &mut self.admin;
+        env_value("ADMIN_USERNAME", &mut admin.username, problems);
+        env_value("ADMIN_PASSWORD", &mut admin.password, problems);
+        env_value("ADMIN_EMAIL", &mut admin.email, problems);
+
+        let mail = &mut self.mail;
+        env_value("MAIL_TRANSPORT", &mut mail.transport, problems);
+        env_value("MAIL_FROM", &mut mail.from, problems);
+        env_value("MAIL_OUTBOX_DIR", &mut mail.outbox_dir, problems);
+        env_optional("SMTP_HOST", &mut mail.smtp_host, problems);
+        env_value("SMTP_PORT", &mut mail.smtp_port, problems);
+        env_optional("SMTP_USERNAME", &mut mail.smtp_username, problems);
+        env_optional("SMTP_PASSWORD", &mut mail.smtp_password, problems);
+
+        env_value("TOTP_ISSUER", &mut self.two_factor.issuer, problems);
+
+        env_value("SEARCH_LANGUAGE", &mut self.search.language, problems);
+
+        let mut names = Vec::new();
+        env_list("OIDC_PROVIDERS", ',', &mut names);
+        for name in names {
+            let prefix = format!("OIDC_{}_", name.to_uppercase());
+            let provider = self.oidc.entry(name).or_default();
+            env_value(&format!("{}ISSUER_URL", prefix), &mut provider.issuer_url, problems);
+            env_value(&format!("{}CLIENT_ID", prefix), &mut provider.client_id, problems);
+            env_optional(&format!("{}CLIENT_SECRET", prefix), &mut provider.client_secret, problems);
+            env_optional(&format!("{}REDIRECT_URL", prefix), &mut provider.redirect_url, problems);
+            env_list(&format!("{}SCOPES", prefix), ' ', &mut provider.scopes);
+        }
+
+        let features = &mut self.features;
+        env_value("FEATURE_REGISTRATION", &mut features.registration, problems);
+        env_value("FEATURE_LEGACY_LOGIN", &mut features.legacy_login, problems);
+        env_value("FEATURE_IMPERSONATION", &mut features.impersonation, problems);
+    }
+
+    fn apply_cli(&mut self, cli: &Cli) {
+        if let Some(bind) = &cli.bind {
+            self.server.bind_address = bind.clone();
+        }
+        if let Some(workers) = cli.workers {
+            self.server.workers = Some(workers);
+        }
+        if let Some(url) = &cli.database_url {
+            self.database.url = url.clone();
+        }
+        if let Some(max_connections) = cli.max_connections {
+            self.database.max_connections = max_connections;
+        }
+    }
+
+    fn validate_database(&self, problems: &mut Vec<String>) {
+        let mut require = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+
+        require(!self.database.url.is_empty(), "database.url (DATABASE_URL) must be set");
+        require(
+            self.database.max_connections >= 1,
+            "database.max_connections (DATABASE_MAX_CONNECTIONS) must be at least 1",
+        );
+        require(
+            self.database.min_connections <= self.database.max_connections,
+            "database.min_connections must not be above database.max_connections",
+        );
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        let mut require = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+
+        require(
+            self.server.bind_address.parse::<SocketAddr>().is_ok(),
+            "server.bind_address (BIND_ADDRESS) must look like 127.0.0.1:8080",
+        );
+        require(self.server.workers != Some(0), "server.workers (WORKERS) must be at least 1");
+        require(!self.server.base_url.is_empty(), "server.base_url (APP_BASE_URL) must be set");
+
+        require(self.tokens.access_ttl_seconds > 0, "tokens.access_ttl_seconds must be positive");
+        require(self.tokens.refresh_ttl_days > 0, "tokens.refresh_ttl_days must be positive");
+        require(self.tokens.impersonation_ttl_seconds > 0, "tokens.impersonation_ttl_seconds must be positive");
+        require(self.tokens.password_reset_ttl_minutes > 0, "tokens.password_reset_ttl_minutes must be positive");
+
+        let jwt = &self.jwt;
+        match &jwt.signing_key_file {
+            Some(_) => {
+                require(jwt.signing_key_id.is_some(), "jwt.signing_key_id (JWT_SIGNING_KEY_ID) must be set with a signing key file");
+                require(
+                    jwt.signing_public_key_file.is_some(),
+                    "jwt.signing_public_key_file (JWT_SIGNING_PUBLIC_KEY_FILE) must be set with a signing key file",
+                );
+                require(
+                    is_asymmetric_algorithm(&jwt.signing_algorithm),
+                    "jwt.signing_algorithm (JWT_SIGNING_ALGORITHM) must be RS256 or EdDSA",
+                );
+            }
+            None => require(
+                jwt.secret.is_some(),
+                "jwt.secret (JWT_SECRET) must be set when no signing key file is configured",
+            ),
+        }
+        for entry in &jwt.verification_keys {
+            let parts: Vec<&str> = entry.splitn(3, ':').collect();
+            require(
+                parts.len() == 3 && is_asymmetric_algorithm(parts[1]),
+                &format!("jwt.verification_keys entry {:?} must look like kid:RS256|EdDSA:path", entry),
+            );
+        }
+
+        let passwords = &self.passwords;
+        require(
+            Params::new(
+                passwords.argon2_memory_kib,
+                passwords.argon2_iterations,
+                passwords.argon2_parallelism,
+                None,
+            )
+            .is_ok(),
+            "passwords.argon2_* (ARGON2_*) are not valid Argon2 parameters",
+        );
+        require(passwords.min_length >= 1, "passwords.min_length (PASSWORD_MIN_LENGTH) must be at least 1");
+        require(
+            passwords.min_entropy_bits >= 0.0,
+            "passwords.min_entropy_bits (PASSWORD_MIN_ENTROPY_BITS) must not be negative",
+        );
+
+        require(!self.admin.username.is_empty(), "admin.username (ADMIN_USERNAME) must be set");
+        require(!self.admin.password.is_empty(), "admin.password (ADMIN_PASSWORD) must be set");
+        require(!self.admin.email.is_empty(), "admin.email (ADMIN_EMAIL) must be set");
+
+        match self.mail.transport.as_str() {
+            "file" => {}
+            "smtp" => require(self.mail.smtp_host.is_some(), "mail.smtp_host (SMTP_HOST) must be set for the smtp transport"),
+            _ => require(false, "mail.transport (MAIL_TRANSPORT) must be file or smtp"),
+        }
+
+        require(
+            !self.search.language.is_empty()
+                && self
+                    .search
+                    .language
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.'),
+            "search.language (SEARCH_LANGUAGE) must name a text search configuration like english",
+        );
+
+        for (name, provider) in &self.oidc {
+            require(
+                !provider.issuer_url.is_empty(),
+                &format!("oidc.{}.issuer_url (OIDC_{}_ISSUER_URL) must be set", name, name.to_uppercase()),
+            );
+            require(
+                !provider.client_id.is_empty(),
+                &format!("oidc.{}.client_id (OIDC_{}_CLIENT_ID) must be set", name, name.to_uppercase()),
+            );
+        }
+
+        // Reads and parses the key files once the settings naming them are complete,
+        // so a bad path is reported here instead of failing later.
+        if !problems.iter().any(|problem| problem.starts_with("jwt.")) {
+            if let Err(error) = KeyStore::from_config(&self.jwt) {
+                problems.push(error);
+            }
+        }
+    }
+}
+
+fn is_asymmetric_algorithm(algorithm: &str) -> bool {
+    matches!(algorithm, "RS256" | "EdDSA")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    use clap::Parser;
+
+    use super::{Cli, Config};
+
+    /// Tests that set environment variables hold this, the environment is shared by the whole process.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    fn write_temp(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("config-test-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Settings that pass validation, for tests that break one thing at a time.
+    fn valid_config() -> Config {
+        let mut config = Config::default();
+        config.database.url = "postgres://localhost/app".to_string();
+        config.jwt.secret = Some("secret".to_string());
+        config.admin.username = "admin".to_string();
+        config.admin.password = "adminPass".to_string();
+        config.admin.email = "admin@example.com".to_string();
+        config
+    }
+
+    fn problems(config: &Config) -> Vec<String> {
+        let mut problems = Vec::new();
+        config.validate_database(&mut problems);
+        config.validate(&mut problems);
+        problems
+    }
+
+    #[test]
+    fn cli_beats_env_beats_toml_beats_defaults() {
+        let _env = ENV.lock().unwrap();
+        let path = write_temp(
+            "layers.toml",
+            r#"
+            [server]
+            bind_address = "127.0.0.1:1000"
+            workers = 2
+
+            [database]
+            url = "postgres://localhost/app"
+            max_connections = 3
+            "#,
+        );
+        std::env::set_var("BIND_ADDRESS", "127.0.0.1:2000");
+        std::env::set_var("WORKERS", "4");
+
+        let cli = Cli::parse_from([
+            "app",
+            "--config",
+            path.to_str().unwrap(),
+            "--bind",
+            "127.0.0.1:3000",
+            "--migrate-only",
+        ]);
+        let config = Config::load(&cli);
+
+        std::env::remove_var("BIND_ADDRESS");
+        std::env::remove_var("WORKERS");
+        std::fs::remove_file(&path).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.server.bind_address, "127.0.0.1:3000");
+        assert_eq!(config.server.workers, Some(4));
+        assert_eq!(config.database.max_connections, 3);
+        assert_eq!(config.database.min_connections, Config::default().database.min_connections);
+    }
+
+    #[test]
+    fn reports_env_values_that_do_not_parse() {
+        let _env = ENV.lock().unwrap();
+        let path = write_temp("env.toml", "[database]\nurl = \"postgres://localhost/app\"\n");
+        std::env::set_var("WORKERS", "many");
+
+        let cli = Cli::parse_from(["app", "--config", path.to_str().unwrap(), "--migrate-only"]);
+        let error = Config::load(&cli).err();
+
+        std::env::remove_var("WORKERS");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(error.unwrap().0, vec!["WORKERS has an invalid value \"many\"".to_string()]);
+    }
+
+    #[test]
+    fn reports_unknown_toml_settings() {
+        let path = write_temp("unknown.toml", "[server]\nbind = \"127.0.0.1:1000\"\n");
+
+        let cli = Cli::parse_from(["app", "--config", path.to_str().unwrap()]);
+        let error = Config::load(&cli).err();
+        std::fs::remove_file(&path).unwrap();
+
+        let problems = error.unwrap().0;
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("unknown field `bind`"), "{:?}", problems);
+    }
+
+    #[test]
+    fn valid_settings_pass() {
+        assert_eq!(problems(&valid_config()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_every_missing_setting_together() {
+        let problems = problems(&Config::default());
+
+        for expected in [
+            "database.url (DATABASE_URL) must be set",
+            "jwt.secret (JWT_SECRET) must be set when no signing key file is configured",
+            "admin.username (ADMIN_USERNAME) must be set",
+            "admin.password (ADMIN_PASSWORD) must be set",
+            "admin.email (ADMIN_EMAIL) must be set",
+        ] {
+            assert!(problems.iter().any(|problem| problem == expected), "{} missing in {:?}", expected, problems);
+        }
+    }
+
+    #[test]
+    fn reports_invalid_values() {
+        let mut config = valid_config();
+        config.server.bind_address = "localhost".to_string();
+        config.server.workers = Some(0);
+        config.database.min_connections = 10;
+        config.tokens.access_ttl_seconds = 0;
+        config.mail.transport = "carrier-pigeon".to_string();
+        config.search.language = "english; DROP TABLE articles".to_string();
+
+        assert_eq!(
+            problems(&config),
+            vec![
+                "database.min_connections must not be above database.max_connections",
+                "server.bind_address (BIND_ADDRESS) must look like 127.0.0.1:8080",
+                "server.workers (WORKERS) must be at least 1",
+                "tokens.access_ttl_seconds must be positive",
+                "mail.transport (MAIL_TRANSPORT) must be file or smtp",
+                "search.language (SEARCH_LANGUAGE) must name a text search configuration like english",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_that_key_files_exist_and_parse() {
+        let mut config = valid_config();
+        config.jwt.signing_key_file = Some(PathBuf::from("/nonexistent/signing.pem"));
+        config.jwt.signing_public_key_file = Some(PathBuf::from("/nonexistent/signing.pub.pem"));
+        config.jwt.signing_key_id = Some("2026-10".to_string());
+        let problems_found = problems(&config);
+        assert_eq!(problems_found.len(), 1);
+        assert!(problems_found[0].starts_with("Cannot read key file /nonexistent/signing.pem"), "{:?}", problems_found);
+
+        let garbage = write_temp("garbage.pem", "not a key");
+        config.jwt.signing_key_file = Some(garbage.clone());
+        let problems_found = problems(&config);
+        assert_eq!(problems_found.len(), 1);
+        assert!(problems_found[0].starts_with("Invalid JWT signing key"), "{:?}", problems_found);
+
+        let mut config = valid_config();
+        config.jwt.verification_keys = vec![format!("2026-09:RS256:{}", garbage.display())];
+        let problems_found = problems(&config);
+        assert_eq!(problems_found.len(), 1);
+        assert!(problems_found[0].starts_with("Invalid RSA public key for 2026-09"), "{:?}", problems_found);
+
+        config.jwt.verification_keys = vec!["2026-09:RS256".to_string()];
+        assert_eq!(
+            problems(&config),
+            vec!["jwt.verification_keys entry \"2026-09:RS256\" must look like kid:RS256|EdDSA:path"]
+        );
+        std::fs::remove_file(&garbage).unwrap();
+    }
+}
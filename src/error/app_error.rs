@@ -0,0 +1,436 @@
+use std::fmt;
+
+use actix_web::{
+    http::{header, StatusCode},
+    HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::error::ErrorKind;
+
+use crate::mail::MailError;
+use crate::users::models::AccountStatus;
+use crate::users::password_policy::PolicyViolation;
+
+/// Everything a handler can fail with. Each variant answers with an RFC 7807
+/// problem document carrying a stable `code`, internal errors are logged and
+/// never shown to the client.
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    PasswordPolicy(Vec<PolicyViolation>),
+    AccountBlocked(AccountStatus),
+    TooManyRequests { retry_after: i64 },
+    BadGateway(String),
+    Database(sqlx::Error),
+    Internal(String),
+}
+
+impl AppError {
+    pub fn bad_request(detail: impl Into<String>) -> Self {
+        AppError::BadRequest(detail.into())
+    }
+
+    pub fn unauthorized(detail: impl Into<String>) -> Self {
+        AppError::Unauthorized(detail.into())
+    }
+
+    pub fn forbidden(detail: impl Into<String>) -> Self {
+        AppError::Forbidden(detail.into())
+    }
+
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        AppError::NotFound(detail.into())
+    }
+
+    pub fn conflict(detail: impl Into<String>) -> Self {
+        AppError::Conflict(detail.into())
+    }
+
+    pub fn bad_gateway(detail: impl Into<String>) -> Self {
+        AppError::BadGateway(detail.into())
+    }
+
+    pub fn internal(detail: impl fmt::Debug) -> Self {
+        AppError::Internal(format!("{:?}", detail))
+    }
+
+    /// The machine-readable code clients can match on, it never changes for a given kind of error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::PasswordPolicy(_) => "password_policy",
+            AppError::AccountBlocked(_) => "account_blocked",
+            AppError::TooManyRequests { .. } => "too_many_requests",
+            AppError::BadGateway(_) => "bad_gateway",
+            AppError::Database(sqlx::Error::RowNotFound) => "not_found",
+            AppError::Database(error) => match database_error_kind(error) {
+                Some(ErrorKind::UniqueViolation) => "unique_violation",
+                Some(ErrorKind::ForeignKeyViolation) => "foreign_key_violation",
+                _ => "internal_error",
+            },
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            AppError::BadRequest(detail)
+            | AppError::Unauthorized(detail)
+            | AppError::Forbidden(detail)
+            | AppError::NotFound(detail)
+            | AppError::Conflict(detail)
+            | AppError::BadGateway(detail) => detail.clone(),
+            AppError::PasswordPolicy(_) => "Password does not meet the password policy".to_string(),
+            AppError::AccountBlocked(_) => "This account can't be used right now".to_string(),
+            AppError::TooManyRequests { .. } => "Too many failed login attempts, try again later".to_string(),
+            AppError::Database(sqlx::Error::RowNotFound) => "The requested resource does not exist".to_string(),
+            AppError::Database(error) => match database_error_kind(error) {
+                Some(ErrorKind::UniqueViolation) => "A resource with these values already exists".to_string(),
+                Some(ErrorKind::ForeignKeyViolation) => {
+                    "The request refers to a resource that does not exist or is still in use".to_string()
+                }
+                _ => "An internal error occurred".to_string(),
+            },
+            AppError::Internal(_) => "An internal error occurred".to_string(),
+        }
+    }
+
+    fn extensions(&self) -> Map<String, Value> {
+        let mut extensions = Map::new();
+
+        match self {
+            AppError::PasswordPolicy(violations) => {
+                extensions.insert("violations".to_string(), serde_json::to_value(violations).unwrap_or_default());
+            }
+            AppError::AccountBlocked(status) => {
+                // Renamed so it doesn't clash with the HTTP status of the problem.
+                extensions.insert("account_status".to_string(), Value::from(status.status.clone()));
+                extensions.insert(
+                    "suspended_until".to_string(),
+                    serde_json::to_value(status.suspended_until).unwrap_or_default(),
+                );
+                extensions.insert("status_reason".to_string(), Value::from(status.status_reason.clone()));
+            }
+            AppError::TooManyRequests { retry_after } => {
+                extensions.insert("retry_after".to_string(), Value::from(*retry_after));
+            }
+            AppError::Database(error) => {
+                if let Some(constraint) = error.as_database_error().and_then(|error| error.constraint()) {
+                    extensions.insert("constraint".to_string(), Value::from(constraint));
+                }
+            }
+            _ => {}
+        }
+
+        extensions
+    }
+}
+
+fn database_error_kind(error: &sqlx::Error) -> Option<ErrorKind> {
+    error.as_database_error().map(|error| error.kind())
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Database(error) => write!(f, "Database error: {:?}", error),
+            AppError::Internal(detail) => write!(f, "Internal error: {}", detail),
+            _ => write!(f, "{}", self.detail()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Problem {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: String,
+    status: u16,
+    detail: String,
+    code: &'static str,
+    #[serde(flatten)]
+    extensions: Map<String, Value>,
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) | AppError::AccountBlocked(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::PasswordPolicy(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            AppError::Database(sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
+            AppError::Database(error) => match database_error_kind(error) {
+                Some(ErrorKind::UniqueViolation) | Some(ErrorKind::ForeignKeyViolation) => StatusCode::CONFLICT,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+
+        if status.is_server_error() {
+            eprintln!("{}", self);
+        }
+
+        let mut response = HttpResponse::build(status);
+        response.insert_header((header::CONTENT_TYPE, "application/problem+json"));
+
+        if let AppError::TooManyRequests { retry_after } = self {
+            response.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+        }
+
+        let code = self.code();
+        let body = Problem {
+            problem_type: format!("/problems/{}", code.replace('_', "-")),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: self.detail(),
+            code,
+            extensions: self.extensions(),
+        };
+
+        response.body(serde_json::to_string(&body).unwrap_or_default())
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(error: sqlx::Error) -> Self {
+        AppError::Database(error)
+    }
+}
+
+impl From<MailError> for AppError {
+    fn from(error: MailError) -> Self {
+        AppError::Internal(error.to_string())
+    }
+}
+
+impl From<actix_web::error::BlockingError> for AppError {
+    fn from(error: actix_web::error::BlockingError) -> Self {
+        AppError::internal(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::error::Error as StdError;
+
+    use actix_web::body::to_bytes;
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+    use sqlx::error::DatabaseError;
+
+    use super::*;
+
+    /// Stands in for the Postgres error of a violated constraint.
+    #[derive(Debug)]
+    struct ConstraintError {
+        sqlstate: &'static str,
+        constraint: &'static str,
+    }
+
+    impl fmt::Display for ConstraintError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "violates {}", self.constraint)
+        }
+    }
+
+    impl StdError for ConstraintError {}
+
+    impl DatabaseError for ConstraintError {
+        fn message(&self) -> &str {
+            "constraint violated"
+        }
+
+        fn as_error(&self) -> &(dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn StdError + Send + Sync + 'static> {
+            self
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(self.sqlstate))
+        }
+
+        fn constraint(&self) -> Option<&str> {
+            Some(self.constraint)
+        }
+
+        fn kind(&self) -> ErrorKind {
+            match self.sqlstate {
+                "23505" => ErrorKind::UniqueViolation,
+                "23503" => ErrorKind::ForeignKeyViolation,
+                "23514" => ErrorKind::CheckViolation,
+                _ => ErrorKind::Other,
+            }
+        }
+    }
+
+    fn violation(sqlstate: &'static str, constraint: &'static str) -> AppError {
+        AppError::from(sqlx::Error::Database(Box::new(ConstraintError { sqlstate, constraint })))
+    }
+
+    async fn problem(error: AppError) -> (u16, String, Value) {
+        let response = error.error_response();
+        let status = response.status().as_u16();
+        let content_type = response.headers().get(header::CONTENT_TYPE).unwrap().to_str().unwrap().to_string();
+        let body = to_bytes(response.into_body()).await.unwrap();
+
+        (status, content_type, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn answers_with_a_problem_document() {
+        let (status, content_type, body) = problem(AppError::not_found("Article not found")).await;
+
+        assert_eq!(status, 404);
+        assert_eq!(content_type, "application/problem+json");
+        assert_eq!(
+            body,
+            json!({
+                "type": "/problems/not-found",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "Article not found",
+                "code": "not_found",
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn maps_every_kind_to_its_status_and_code() {
+        let cases = [
+            (AppError::bad_request("x"), 400, "bad_request"),
+            (AppError::unauthorized("x"), 401, "unauthorized"),
+            (AppError::forbidden("x"), 403, "forbidden"),
+            (AppError::not_found("x"), 404, "not_found"),
+            (AppError::conflict("x"), 409, "conflict"),
+            (AppError::PasswordPolicy(vec![]), 422, "password_policy"),
+            (AppError::TooManyRequests { retry_after: 1 }, 429, "too_many_requests"),
+            (AppError::bad_gateway("x"), 502, "bad_gateway"),
+            (AppError::internal("x"), 500, "internal_error"),
+            (AppError::from(sqlx::Error::RowNotFound), 404, "not_found"),
+            (AppError::from(sqlx::Error::PoolTimedOut), 500, "internal_error"),
+        ];
+
+        for (error, status, code) in cases {
+            let (actual_status, _, body) = problem(error).await;
+            assert_eq!((actual_status, body["code"].as_str().unwrap()), (status, code));
+            assert_eq!(body["status"], status);
+            assert_eq!(body["type"], format!("/problems/{}", code.replace('_', "-")));
+        }
+    }
+
+    #[actix_web::test]
+    async fn internal_errors_keep_their_details_to_the_log() {
+        let (_, _, body) = problem(AppError::internal("connection string with a password")).await;
+        assert_eq!(body["detail"], "An internal error occurred");
+
+        let (_, _, body) = problem(AppError::from(sqlx::Error::PoolTimedOut)).await;
+        assert_eq!(body["detail"], "An internal error occurred");
+    }
+
+    #[actix_web::test]
+    async fn constraint_violations_are_conflicts() {
+        let (status, _, body) = problem(violation("23505", "users_email_key")).await;
+        assert_eq!(status, 409);
+        assert_eq!(
+            body,
+            json!({
+                "type": "/problems/unique-violation",
+                "title": "Conflict",
+                "status": 409,
+                "detail": "A resource with these values already exists",
+                "code": "unique_violation",
+                "constraint": "users_email_key",
+            })
+        );
+
+        let (status, _, body) = problem(violation("23503", "articles_published_by_fkey")).await;
+        assert_eq!(status, 409);
+        assert_eq!(body["code"], "foreign_key_violation");
+        assert_eq!(body["constraint"], "articles_published_by_fkey");
+
+        let (status, _, body) = problem(violation("23514", "users_status_check")).await;
+        assert_eq!(status, 500);
+        assert_eq!(body["code"], "internal_error");
+    }
+
+    #[actix_web::test]
+    async fn row_not_found_is_a_404() {
+        let (_, _, body) = problem(AppError::from(sqlx::Error::RowNotFound)).await;
+
+        assert_eq!(
+            body,
+            json!({
+                "type": "/problems/not-found",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "The requested resource does not exist",
+                "code": "not_found",
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn adds_the_details_of_each_kind() {
+        let violations = vec![PolicyViolation {
+            rule: "min_length",
+            message: "Password must be at least 10 characters long".to_string(),
+        }];
+        let (_, _, body) = problem(AppError::PasswordPolicy(violations)).await;
+        assert_eq!(
+            body["violations"],
+            json!([{ "rule": "min_length", "message": "Password must be at least 10 characters long" }])
+        );
+
+        let response = AppError::TooManyRequests { retry_after: 30 }.error_response();
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "30");
+        let (_, _, body) = problem(AppError::TooManyRequests { retry_after: 30 }).await;
+        assert_eq!(body["retry_after"], 30);
+
+        let blocked = AppError::AccountBlocked(AccountStatus {
+            status: "suspended".to_string(),
+            suspended_until: Some(Utc.with_ymd_and_hms(2026, 11, 1, 0, 0, 0).unwrap()),
+            status_reason: Some("Spam".to_string()),
+        });
+        let (status, _, body) = problem(blocked).await;
+        assert_eq!(status, 403);
+        assert_eq!(
+            body,
+            json!({
+                "type": "/problems/account-blocked",
+                "title": "Forbidden",
+                "status": 403,
+                "detail": "This account can't be used right now",
+                "code": "account_blocked",
+                "account_status": "suspended",
+                "suspended_until": "2026-11-01T00:00:00Z",
+                "status_reason": "Spam",
+            })
+        );
+    }
+}
@@ -0,0 +1,3 @@
+pub mod app_error;
+
+pub use app_error::AppError;
@@ -0,0 +1,149 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use actix_web::web;
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+
+use crate::config::MailConfig;
+
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub struct MailError(pub String);
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to send email: {}", self.0)
+    }
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), MailError>;
+}
+
+/// Delivers mail through an SMTP relay over TLS.
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    pub fn new(
+        from: &str,
+        host: &str,
+        port: u16,
+        credentials: Option<(String, String)>,
+    ) -> Result<Self, MailError> {
+        let from = from.parse().map_err(|error| MailError(format!("{:?}", error)))?;
+
+        let mut transport = AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+            .map_err(|error| MailError(format!("{:?}", error)))?
+            .port(port);
+
+        if let Some((username, password)) = credentials {
+            transport = transport.credentials(Credentials::new(username, password));
+        }
+
+        Ok(SmtpMailer {
+            from,
+            transport: transport.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> Result<(), MailError> {
+        let to: Mailbox = email
+            .to
+            .parse()
+            .map_err(|error| MailError(format!("{:?}", error)))?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(email.subject)
+            .body(email.body)
+            .map_err(|error| MailError(format!("{:?}", error)))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|error| MailError(format!("{:?}", error)))
+    }
+}
+
+/// Writes every message to a file in an outbox directory instead of sending it,
+/// so the flows that send mail can be exercised without an SMTP server.
+pub struct FileMailer {
+    from: String,
+    outbox: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(from: &str, outbox: impl Into<PathBuf>) -> Self {
+        FileMailer {
+            from: from.to_string(),
+            outbox: outbox.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), MailError> {
+        let now = Utc::now();
+        let path = self
+            .outbox
+            .join(format!("{}-{}.eml", now.format("%Y%m%d%H%M%S%f"), rand::random::<u32>()));
+        let contents = format!(
+            "From: {}\r\nTo: {}\r\nDate: {}\r\nSubject: {}\r\n\r\n{}\r\n",
+            self.from,
+            email.to,
+            now.to_rfc2822(),
+            email.subject,
+            email.body
+        );
+        let outbox = self.outbox.clone();
+
+        web::block(move || {
+            fs::create_dir_all(&outbox)?;
+            fs::write(path, contents)
+        })
+        .await
+        .map_err(|error| MailError(format!("{:?}", error)))?
+        .map_err(|error| MailError(format!("{:?}", error)))
+    }
+}
+
+/// Builds the mailer selected by `transport` (`smtp` or `file`, the default).
+pub fn mailer_from_config(config: &MailConfig) -> Arc<dyn Mailer> {
+    match config.transport.as_str() {
+        "smtp" => {
+            let host = config.smtp_host.as_deref().expect("mail.smtp_host must be set");
+            let credentials = match (&config.smtp_username, &config.smtp_password) {
+                (Some(username), Some(password)) => Some((username.clone(), password.clone())),
+                _ => None,
+            };
+
+            Arc::new(
+                SmtpMailer::new(&config.from, host, config.smtp_port, credentials)
+                    .expect("Error building the SMTP transport"),
+            )
+        }
+        _ => Arc::new(FileMailer::new(&config.from, config.outbox_dir.clone())),
+    }
+}
@@ -0,0 +1,3 @@
+pub mod mailer;
+
+pub use mailer::{mailer_from_config, Email, MailError, Mailer};
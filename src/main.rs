@@ -1,17 +1,49 @@
+use actix::Actor;
 use actix_web::{
     web::{self, Data}, App, HttpServer
 };
 use actix_web_httpauth::middleware::HttpAuthentication;
+use clap::Parser;
 use dotenv::dotenv;
 use sqlx::postgres::PgPoolOptions;
+use std::sync::Arc;
+use std::time::Duration;
 mod users;
-use users::{login, register, update_email,update_password,update_username};
+use users::{
+    ban_user, confirm_two_factor, create_api_key, delete_user, export_user_data, forgot_password,
+    get_api_keys, get_sessions, legacy_login, login, login_two_factor, login_two_factor_confirm,
+    login_two_factor_setup, reactivate_user, register, resend_verification_email, reset_password,
+    revoke_api_key, revoke_user_session, set_role_mfa_policy, setup_two_factor, suspend_user,
+    unlock_user, update_email, update_password, update_username, verify_email, PasswordPolicy,
+};
 
 mod articles;
-use articles::{create_article, delete_article, get_all_articles, get_article, update_article_content,update_article_title};
+use articles::{
+    approve_article, create_article, delete_article, get_all_articles, get_article, reject_article,
+    schedule_article, search_articles, submit_article, sync_search_language, unpublish_article,
+    update_article_content, update_article_title, ArticleScheduler,
+};
 
 mod auth;
-use auth::{validator, AppState, TokenClaims};
+use auth::{
+    impersonate, jwks, logout, logout_all, oidc_callback, oidc_login, refresh_access_token,
+    validator, AppState, KeyStore, OidcProviders, PasswordHasher, RevocationPurger, TokenClaims,
+};
+
+mod config;
+use config::{Cli, Config};
+
+mod error;
+use error::AppError;
+
+mod mail;
+use mail::mailer_from_config;
+
+mod migrate;
+use migrate::{check_migrations, MIGRATOR};
+
+mod roles;
+use roles::{assign_role, create_role, delete_role, get_permissions, get_roles, update_role_permissions};
 
 mod seed;
 use seed::seed_admin_user;
@@ -19,38 +51,151 @@ use seed::seed_admin_user;
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let cli = Cli::parse();
+    let config = Config::load(&cli).unwrap_or_else(|error| {
+        eprint!("{}", error);
+        std::process::exit(1);
+    });
+
     let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&database_url)
+        .max_connections(config.database.max_connections)
+        .min_connections(config.database.min_connections)
+        .acquire_timeout(Duration::from_secs(config.database.acquire_timeout_seconds))
+        .idle_timeout(config.database.idle_timeout_seconds.map(Duration::from_secs))
+        .connect(&config.database.url)
         .await
         .expect("Error building a connection pool");
 
-    seed_admin_user(&pool)
+    if cli.check_migrations {
+        let report = check_migrations(&pool).await.expect("Error reading the applied migrations");
+        print!("{}", report);
+        std::process::exit(if report.is_clean() { 0 } else { 1 });
+    }
+
+    MIGRATOR.run(&pool).await.expect("Error applying database migrations");
+
+    if cli.migrate_only {
+        println!("Database migrations applied");
+        return Ok(());
+    }
+
+    sync_search_language(&pool, &config.search.language)
+        .await
+        .expect("Error applying search.language, is it a text search configuration Postgres knows?");
+
+    let hasher = Arc::new(PasswordHasher::from_config(&config.passwords));
+
+    seed_admin_user(&pool, &hasher, &config.admin)
         .await
         .expect("Failed to seed admin user");
 
-    HttpServer::new(move || {
+    RevocationPurger { db: pool.clone() }.start();
+    ArticleScheduler { db: pool.clone() }.start();
+
+    let mailer = mailer_from_config(&config.mail);
+    let oidc = Arc::new(OidcProviders::from_config(&config));
+    let keys = Arc::new(KeyStore::from_config(&config.jwt).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }));
+    if config.jwt.signing_key_file.is_none() {
+        eprintln!(
+            "Warning: no jwt.signing_key_file (JWT_SIGNING_KEY_FILE) set, tokens are signed with HS256 \
+            and /.well-known/jwks.json can't publish the key to verify them"
+        );
+    }
+    let password_policy = Arc::new(PasswordPolicy::from_config(&config.passwords));
+    let bind_address = config.server.bind_address.clone();
+    let workers = config.server.workers;
+    let config = Arc::new(config);
+
+    let mut server = HttpServer::new(move || {
         let bearer_middleware = HttpAuthentication::bearer(validator);
+        let features = &config.features;
         App::new()
-            .app_data(Data::new(AppState { db: pool.clone() }))
+            .app_data(Data::new(AppState {
+                db: pool.clone(),
+                mailer: mailer.clone(),
+                oidc: oidc.clone(),
+                keys: keys.clone(),
+                hasher: hasher.clone(),
+                password_policy: password_policy.clone(),
+                config: config.clone(),
+            }))
+            .app_data(web::JsonConfig::default().error_handler(|error, _| AppError::bad_request(error.to_string()).into()))
+            .app_data(web::PathConfig::default().error_handler(|error, _| AppError::not_found(error.to_string()).into()))
+            .app_data(web::QueryConfig::default().error_handler(|error, _| AppError::bad_request(error.to_string()).into()))
             .service(login)
-            .service(register)
+            .configure(|cfg| {
+                if features.legacy_login {
+                    cfg.service(legacy_login);
+                }
+                if features.registration {
+                    cfg.service(register);
+                }
+            })
+            .service(login_two_factor)
+            .service(login_two_factor_setup)
+            .service(login_two_factor_confirm)
+            .service(oidc_login)
+            .service(oidc_callback)
+            .service(refresh_access_token)
+            .service(jwks)
+            .service(forgot_password)
+            .service(reset_password)
+            .service(verify_email)
             .service(
                 web::scope("")
                     .wrap(bearer_middleware)
+                    .service(logout)
+                    .service(logout_all)
+                    .service(resend_verification_email)
                     .service(create_article)
                     .service(get_all_articles)
+                    .service(search_articles)
                     .service(get_article)
                     .service(delete_article)
                     .service(update_article_content)
                     .service(update_article_title)
+                    .service(submit_article)
+                    .service(approve_article)
+                    .service(reject_article)
+                    .service(unpublish_article)
+                    .service(schedule_article)
                     .service(update_email)
                     .service(update_password)
-                    .service(update_username),
+                    .service(update_username)
+                    .service(delete_user)
+                    .service(export_user_data)
+                    .service(setup_two_factor)
+                    .service(confirm_two_factor)
+                    .service(set_role_mfa_policy)
+                    .service(create_api_key)
+                    .service(get_api_keys)
+                    .service(revoke_api_key)
+                    .service(get_sessions)
+                    .service(revoke_user_session)
+                    .service(get_roles)
+                    .service(get_permissions)
+                    .service(create_role)
+                    .service(update_role_permissions)
+                    .service(delete_role)
+                    .service(assign_role)
+                    .service(unlock_user)
+                    .service(suspend_user)
+                    .service(ban_user)
+                    .service(reactivate_user)
+                    .configure(|cfg| {
+                        if features.impersonation {
+                            cfg.service(impersonate);
+                        }
+                    }),
             )
-    })
-    .bind(("127.0.0.1", 8080))?
-    .run()
-    .await
+    });
+
+    if let Some(workers) = workers {
+        server = server.workers(workers);
+    }
+
+    server.bind(bind_address)?.run().await
 }
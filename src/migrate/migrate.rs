@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::PgPool;
+
+/// The `migrations` folder, embedded at compile time. The baseline migration is
+/// dated before the others because they build on its tables, so databases that
+/// applied those first get it out of order. Applied versions this build doesn't
+/// know are let through for the same databases, like `sqlx migrate run --ignore-missing`.
+pub static MIGRATOR: Migrator = Migrator {
+    ignore_missing: true,
+    ..sqlx::migrate!()
+};
+
+/// How the database differs from the migrations this binary was built with.
+#[derive(Default)]
+pub struct MigrationReport {
+    /// Not applied yet, with their description.
+    pub pending: Vec<(i64, String)>,
+    /// Applied, but the file changed since.
+    pub modified: Vec<i64>,
+    /// Applied, but unknown to this binary.
+    pub unknown: Vec<i64>,
+    /// A migration that failed halfway and needs fixing by hand.
+    pub failed: Option<i64>,
+}
+
+impl MigrationReport {
+    pub fn is_clean(&self) -> bool {
+        self.pending.is_empty() && self.modified.is_empty() && self.unknown.is_empty() && self.failed.is_none()
+    }
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_clean() {
+            return writeln!(f, "Database schema is up to date");
+        }
+
+        if let Some(version) = self.failed {
+            writeln!(f, "Failed migration: {}", version)?;
+        }
+        for (version, description) in &self.pending {
+            writeln!(f, "Pending migration: {} {}", version, description)?;
+        }
+        for version in &self.modified {
+            writeln!(f, "Applied migration was changed afterwards: {}", version)?;
+        }
+        for version in &self.unknown {
+            writeln!(f, "Applied migration is missing from this build: {}", version)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares the applied migrations with the embedded ones without changing anything.
+pub async fn check_migrations(db: &PgPool) -> Result<MigrationReport, MigrateError> {
+    let mut report = MigrationReport::default();
+
+    let has_table = sqlx::query_scalar::<_, bool>("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(db)
+        .await?;
+
+    let applied: HashMap<i64, Vec<u8>> = if has_table {
+        let mut conn = db.acquire().await?;
+        report.failed = conn.dirty_version().await?;
+
+        conn.list_applied_migrations()
+            .await?
+            .into_iter()
+            .map(|migration| (migration.version, migration.checksum.into_owned()))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    for migration in MIGRATOR.iter().filter(|migration| !migration.migration_type.is_down_migration()) {
+        match applied.get(&migration.version) {
+            Some(checksum) if *checksum != *migration.checksum => report.modified.push(migration.version),
+            Some(_) => {}
+            None => report
+                .pending
+                .push((migration.version, migration.description.to_string())),
+        }
+    }
+
+    report.unknown = applied
+        .keys()
+        .copied()
+        .filter(|version| !MIGRATOR.version_exists(*version))
+        .collect();
+    report.unknown.sort();
+
+    Ok(report)
+}
@@ -0,0 +1,4 @@
+#[allow(clippy::module_inception)]
+pub mod migrate;
+
+pub use migrate::{check_migrations, MIGRATOR};
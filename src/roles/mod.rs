@@ -0,0 +1,5 @@
+pub mod models;
+#[allow(clippy::module_inception)]
+pub mod roles;
+
+pub use roles::{assign_role, create_role, delete_role, get_permissions, get_roles, update_role_permissions};
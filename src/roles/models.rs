@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{self, FromRow};
+
+#[derive(Serialize, FromRow)]
+pub struct Role {
+    pub name: String,
+    pub description: String,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct PermissionInfo {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateRoleBody {
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateRolePermissionsBody {
+    pub permissions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AssignRoleBody {
+    pub role: String,
+}
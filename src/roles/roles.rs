@@ -0,0 +1,229 @@
+use crate::auth::permissions::{Authorized, Permission};
+use crate::auth::revocation::revoke_access_tokens;
+use crate::error::AppError;
+use crate::roles::models::{
+    AssignRoleBody, CreateRoleBody, PermissionInfo, Role, UpdateRolePermissionsBody,
+};
+use crate::users::models::UserNoPassword;
+use crate::AppState;
+use actix_web::{
+    delete, get, post, put,
+    web::{Data, Json, Path},
+    HttpResponse,
+};
+use regex::Regex;
+use sqlx::PgPool;
+
+/// Roles the app itself depends on, they can't be deleted.
+const BUILT_IN_ROLES: [&str; 2] = ["admin", "user"];
+
+const SELECT_ROLES: &str = "SELECT roles.name, roles.description,
+        COALESCE(array_agg(role_permissions.permission ORDER BY role_permissions.permission)
+                 FILTER (WHERE role_permissions.permission IS NOT NULL), '{}') AS permissions
+    FROM roles
+    LEFT JOIN role_permissions ON role_permissions.role = roles.name";
+
+/// Returns the canonical names, or the first name that isn't a known permission.
+fn parse_permissions(permissions: &[String]) -> Result<Vec<&'static str>, String> {
+    permissions
+        .iter()
+        .map(|permission| {
+            Permission::parse(permission)
+                .map(|permission| permission.as_str())
+                .ok_or_else(|| permission.clone())
+        })
+        .collect()
+}
+
+async fn fetch_role(db: &PgPool, name: &str) -> Result<Role, sqlx::Error> {
+    sqlx::query_as::<_, Role>(&format!(
+        "{} WHERE roles.name = $1 GROUP BY roles.name",
+        SELECT_ROLES
+    ))
+    .bind(name)
+    .fetch_one(db)
+    .await
+}
+
+async fn replace_role_permissions(
+    db: &PgPool,
+    role: &str,
+    description: Option<&str>,
+    permissions: &[&str],
+    create: bool,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    if create {
+        sqlx::query("INSERT INTO roles (name, description) VALUES ($1, $2)")
+            .bind(role)
+            .bind(description.unwrap_or_default())
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    sqlx::query("DELETE FROM role_permissions WHERE role = $1")
+        .bind(role)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("INSERT INTO role_permissions (role, permission) SELECT $1, UNNEST($2::TEXT[])")
+        .bind(role)
+        .bind(permissions)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await
+}
+
+#[get("/roles")]
+async fn get_roles(state: Data<AppState>, auth: Authorized) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::RoleManage)?;
+
+    let roles = sqlx::query_as::<_, Role>(&format!("{} GROUP BY roles.name ORDER BY roles.name", SELECT_ROLES))
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+#[get("/permissions")]
+async fn get_permissions(state: Data<AppState>, auth: Authorized) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::RoleManage)?;
+
+    let permissions = sqlx::query_as::<_, PermissionInfo>("SELECT name, description FROM permissions ORDER BY name")
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(permissions))
+}
+
+#[post("/roles")]
+async fn create_role(
+    state: Data<AppState>,
+    auth: Authorized,
+    body: Json<CreateRoleBody>,
+) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::RoleManage)?;
+
+    let body = body.into_inner();
+
+    let name_regex = Regex::new(r"^[a-z][a-z0-9_-]{1,31}$").unwrap();
+    if !name_regex.is_match(&body.name) {
+        return Err(AppError::bad_request(
+            "Role names are 2-32 lowercase letters, digits, '-' or '_' and start with a letter",
+        ));
+    }
+
+    let permissions = parse_permissions(&body.permissions)
+        .map_err(|unknown| AppError::bad_request(format!("Unknown permission {}", unknown)))?;
+
+    replace_role_permissions(&state.db, &body.name, body.description.as_deref(), &permissions, true)
+        .await
+        .map_err(|error| match error {
+            sqlx::Error::Database(error) if error.is_unique_violation() => AppError::conflict("Role already exists"),
+            error => error.into(),
+        })?;
+
+    let role = fetch_role(&state.db, &body.name).await?;
+
+    Ok(HttpResponse::Created().json(role))
+}
+
+#[put("/roles/{name}/permissions")]
+async fn update_role_permissions(
+    state: Data<AppState>,
+    auth: Authorized,
+    name: Path<String>,
+    body: Json<UpdateRolePermissionsBody>,
+) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::RoleManage)?;
+
+    let name = name.into_inner();
+
+    let permissions = parse_permissions(&body.permissions)
+        .map_err(|unknown| AppError::bad_request(format!("Unknown permission {}", unknown)))?;
+
+    // Taking role:manage away from admin would leave nobody able to give it back.
+    if name == "admin" && !permissions.contains(&Permission::RoleManage.as_str()) {
+        return Err(AppError::bad_request("The admin role must keep role:manage"));
+    }
+
+    fetch_role(&state.db, &name).await.map_err(|error| match error {
+        sqlx::Error::RowNotFound => AppError::not_found("Role not found"),
+        error => error.into(),
+    })?;
+
+    replace_role_permissions(&state.db, &name, None, &permissions, false).await?;
+
+    let role = fetch_role(&state.db, &name).await?;
+
+    Ok(HttpResponse::Ok().json(role))
+}
+
+#[delete("/roles/{name}")]
+async fn delete_role(
+    state: Data<AppState>,
+    auth: Authorized,
+    name: Path<String>,
+) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::RoleManage)?;
+
+    let name = name.into_inner();
+
+    if BUILT_IN_ROLES.contains(&name.as_str()) {
+        return Err(AppError::bad_request("Built-in roles can't be deleted"));
+    }
+
+    let result = sqlx::query("DELETE FROM roles WHERE name = $1")
+        .bind(&name)
+        .execute(&state.db)
+        .await
+        .map_err(|error| match error {
+            sqlx::Error::Database(error) if error.is_foreign_key_violation() => {
+                AppError::conflict("Role is still assigned to users")
+            }
+            error => error.into(),
+        })?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::not_found("Role not found"));
+    }
+
+    Ok(HttpResponse::Ok().json("Role deleted successfully"))
+}
+
+#[put("/user/{id}/role")]
+async fn assign_role(
+    state: Data<AppState>,
+    auth: Authorized,
+    user_id: Path<i32>,
+    body: Json<AssignRoleBody>,
+) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::RoleManage)?;
+    auth.claims.require_not_impersonating()?;
+
+    let user_id = user_id.into_inner();
+
+    if user_id == auth.id() {
+        return Err(AppError::bad_request("You can't change your own role"));
+    }
+
+    let updated_user = sqlx::query_as::<_, UserNoPassword>(
+        "UPDATE users SET role = $1 WHERE id = $2 RETURNING id, username, email, role",
+    )
+    .bind(&body.role)
+    .bind(user_id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|error| match error {
+        sqlx::Error::RowNotFound => AppError::not_found("User not found"),
+        sqlx::Error::Database(error) if error.is_foreign_key_violation() => AppError::bad_request("Unknown role"),
+        error => error.into(),
+    })?;
+
+    // Access tokens carry the old role, the next refresh picks up the new one.
+    revoke_access_tokens(&state, user_id).await?;
+
+    Ok(HttpResponse::Ok().json(updated_user))
+}
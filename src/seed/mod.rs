@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod seed;
 
 pub use seed::seed_admin_user;
\ No newline at end of file
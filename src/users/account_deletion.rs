@@ -0,0 +1,130 @@
+use crate::auth::lockout::account_key;
+use crate::auth::permissions::{Authorized, Permission};
+use crate::error::AppError;
+use crate::users::models::{ArticlePolicy, DeleteUserBody};
+use crate::users::users::verify_current_password;
+use crate::AppState;
+use actix_web::{
+    delete,
+    web::{Bytes, Data, Path},
+    HttpRequest, HttpResponse,
+};
+
+/// Name of the account that owns the articles whose authors deleted their
+/// account anonymously. The account itself is found by `users.is_placeholder`,
+/// the name is only kept away from new accounts.
+pub const DELETED_USER_USERNAME: &str = "deleted-user";
+
+pub fn is_reserved_username(username: &str) -> bool {
+    username.trim().eq_ignore_ascii_case(DELETED_USER_USERNAME)
+}
+
+#[delete("/user/{id}")]
+async fn delete_user(
+    state: Data<AppState>,
+    req: HttpRequest,
+    auth: Authorized,
+    user_id: Path<i32>,
+    body: Bytes,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+    // Only an empty body means the defaults, a typo must not quietly pick them.
+    let body = if body.iter().all(u8::is_ascii_whitespace) {
+        DeleteUserBody::default()
+    } else {
+        serde_json::from_slice::<DeleteUserBody>(&body).map_err(|error| AppError::bad_request(error.to_string()))?
+    };
+
+    auth.claims.require_not_impersonating()?;
+
+    if !auth.owns_or_can(user_id, Permission::UserDeleteAny) {
+        return Err(AppError::forbidden("You can only delete your own account"));
+    }
+
+    let (username, password, is_placeholder) = sqlx::query_as::<_, (String, String, bool)>(
+        "SELECT username, password, is_placeholder FROM users WHERE id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::not_found("User not found"))?;
+
+    if is_placeholder {
+        return Err(AppError::bad_request("The placeholder for deleted accounts cannot be deleted"));
+    }
+
+    if auth.id() == user_id {
+        verify_current_password(&state, &req, &username, body.current_password.as_deref(), &password).await?;
+    }
+
+    let new_author = match body.articles {
+        ArticlePolicy::Delete => None,
+        ArticlePolicy::Anonymize => Some(
+            sqlx::query_scalar::<_, i32>("SELECT id FROM users WHERE is_placeholder")
+                .fetch_one(&state.db)
+                .await?,
+        ),
+        ArticlePolicy::Reassign => match body.reassign_to {
+            Some(new_author) if new_author == user_id => {
+                return Err(AppError::bad_request("Articles cannot be reassigned to the deleted account"))
+            }
+            Some(new_author) => {
+                let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1)")
+                    .bind(new_author)
+                    .fetch_one(&state.db)
+                    .await?;
+
+                if !exists {
+                    return Err(AppError::bad_request("The new author does not exist"));
+                }
+
+                Some(new_author)
+            }
+            None => return Err(AppError::bad_request("reassign_to is required to reassign articles")),
+        },
+    };
+
+    remove_user(&state, user_id, &username, new_author).await?;
+
+    Ok(HttpResponse::Ok().json("Account deleted"))
+}
+
+/// Moves the articles to `new_author`, or deletes them without one, then
+/// removes the account. Everything else the user owns goes with it through
+/// the foreign keys, their sessions included.
+async fn remove_user(
+    state: &AppState,
+    user_id: i32,
+    username: &str,
+    new_author: Option<i32>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = state.db.begin().await?;
+
+    match new_author {
+        Some(new_author) => {
+            sqlx::query("UPDATE articles SET published_by = $1 WHERE published_by = $2")
+                .bind(new_author)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        None => {
+            sqlx::query("DELETE FROM articles WHERE published_by = $1")
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    sqlx::query("DELETE FROM login_failures WHERE key = $1")
+        .bind(account_key(username))
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await
+}
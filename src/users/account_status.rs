@@ -0,0 +1,181 @@
+use crate::auth::permissions::{role_permissions, Authorized, Permission};
+use crate::auth::revocation::revoke_all_tokens;
+use crate::error::AppError;
+use crate::users::models::{AccountStatus, BanUserBody, SuspendUserBody};
+use crate::AppState;
+use actix_web::{
+    post,
+    web::{Data, Json, Path},
+    HttpResponse,
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// The status that keeps the user out, or `None` while the account may be used.
+/// Suspensions that ran out count as active, deleted accounts come back as "deleted".
+pub async fn account_block(db: &PgPool, user_id: i32) -> Result<Option<AccountStatus>, sqlx::Error> {
+    sqlx::query_as::<_, AccountStatus>(
+        "SELECT COALESCE(users.status, 'deleted') AS status, users.suspended_until, users.status_reason
+        FROM (SELECT $1::INTEGER AS id) AS wanted
+        LEFT JOIN users ON users.id = wanted.id
+        WHERE users.id IS NULL
+           OR users.status = 'banned'
+           OR (users.status = 'suspended' AND users.suspended_until > NOW())",
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await
+}
+
+/// Permissions that make an account staff: only someone who holds them too may block it.
+const STAFF_PERMISSIONS: [Permission; 2] = [Permission::UserSuspend, Permission::RoleManage];
+
+fn may_block(caller: &[Permission], target: &[Permission]) -> bool {
+    STAFF_PERMISSIONS
+        .iter()
+        .all(|permission| !target.contains(permission) || caller.contains(permission))
+}
+
+/// Keeps moderators from suspending or banning admins.
+async fn require_may_block(state: &AppState, auth: &Authorized, user_id: i32) -> Result<(), AppError> {
+    let role = sqlx::query_scalar::<_, String>("SELECT role FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::not_found("User not found"))?;
+
+    if !may_block(auth.permissions(), &role_permissions(&state.db, &role).await?) {
+        return Err(AppError::forbidden("You cannot block an account with permissions you don't hold"));
+    }
+    Ok(())
+}
+
+/// Returns `false` when the user doesn't exist. Blocking an account also
+/// revokes everything it is signed in with.
+async fn set_status(
+    state: &AppState,
+    user_id: i32,
+    status: &str,
+    suspended_until: Option<DateTime<Utc>>,
+    reason: Option<String>,
+    hide_articles: bool,
+) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query(
+        "UPDATE users SET status = $1, suspended_until = $2, status_reason = $3, hide_articles = $4
+        WHERE id = $5",
+    )
+    .bind(status)
+    .bind(suspended_until)
+    .bind(reason)
+    .bind(hide_articles)
+    .bind(user_id)
+    .execute(&state.db)
+    .await?;
+
+    if updated.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    if status != "active" {
+        revoke_all_tokens(state, user_id).await?;
+    }
+
+    Ok(true)
+}
+
+#[post("/admin/users/{id}/suspend")]
+async fn suspend_user(
+    state: Data<AppState>,
+    auth: Authorized,
+    user_id: Path<i32>,
+    body: Json<SuspendUserBody>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+    let body = body.into_inner();
+
+    auth.require(Permission::UserSuspend)?;
+
+    if auth.id() == user_id {
+        return Err(AppError::bad_request("You cannot suspend your own account"));
+    }
+
+    if body.until <= Utc::now() {
+        return Err(AppError::bad_request("Suspensions must end in the future"));
+    }
+
+    require_may_block(&state, &auth, user_id).await?;
+
+    if !set_status(&state, user_id, "suspended", Some(body.until), Some(body.reason), false).await? {
+        return Err(AppError::not_found("User not found"));
+    }
+
+    Ok(HttpResponse::Ok().json("Account suspended"))
+}
+
+#[post("/admin/users/{id}/ban")]
+async fn ban_user(
+    state: Data<AppState>,
+    auth: Authorized,
+    user_id: Path<i32>,
+    body: Json<BanUserBody>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+    let body = body.into_inner();
+
+    auth.require(Permission::UserSuspend)?;
+
+    if auth.id() == user_id {
+        return Err(AppError::bad_request("You cannot ban your own account"));
+    }
+
+    require_may_block(&state, &auth, user_id).await?;
+
+    if !set_status(&state, user_id, "banned", None, Some(body.reason), body.hide_articles).await? {
+        return Err(AppError::not_found("User not found"));
+    }
+
+    Ok(HttpResponse::Ok().json("Account banned"))
+}
+
+#[post("/admin/users/{id}/reactivate")]
+async fn reactivate_user(
+    state: Data<AppState>,
+    auth: Authorized,
+    user_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::UserSuspend)?;
+
+    if !set_status(&state, user_id.into_inner(), "active", None, None, false).await? {
+        return Err(AppError::not_found("User not found"));
+    }
+
+    Ok(HttpResponse::Ok().json("Account reactivated"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::may_block;
+    use crate::auth::permissions::Permission;
+
+    const ADMIN: &[Permission] = &[Permission::UserSuspend, Permission::RoleManage, Permission::UserDeleteAny];
+    const MODERATOR: &[Permission] = &[Permission::UserSuspend, Permission::ArticleDeleteAny];
+    const ROLE_MANAGER: &[Permission] = &[Permission::RoleManage];
+    const USER: &[Permission] = &[Permission::ArticleCreate];
+
+    #[test]
+    fn staff_can_only_be_blocked_by_equal_staff() {
+        let cases = [
+            (MODERATOR, USER, true),
+            (MODERATOR, MODERATOR, true),
+            (MODERATOR, ADMIN, false),
+            (MODERATOR, ROLE_MANAGER, false),
+            (ADMIN, MODERATOR, true),
+            (ADMIN, ADMIN, true),
+            (ADMIN, ROLE_MANAGER, true),
+        ];
+
+        for (caller, target, allowed) in cases {
+            assert_eq!(may_block(caller, target), allowed, "{:?} blocking {:?}", caller, target);
+        }
+    }
+}
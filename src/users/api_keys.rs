@@ -0,0 +1,127 @@
+use crate::auth::api_keys::{generate_api_key, ApiScope};
+use crate::auth::permissions::{Authorized, Permission};
+use crate::auth::tokens::hash_token;
+use crate::error::AppError;
+use crate::users::models::{ApiKey, CreateApiKeyBody, CreatedApiKey};
+use crate::{AppState, TokenClaims};
+use actix_web::{
+    delete, get, post,
+    web::{Data, Json, Path, ReqData},
+    HttpResponse,
+};
+use chrono::{Duration, Utc};
+
+#[post("/user/{id}/api-keys")]
+async fn create_api_key(
+    state: Data<AppState>,
+    req_user: Option<ReqData<TokenClaims>>,
+    user_id: Path<i32>,
+    body: Json<CreateApiKeyBody>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+    let body = body.into_inner();
+
+    match req_user {
+        Some(user) if user.id == user_id => {
+            // A key that could mint keys could hand itself any scope.
+            user.require_not_api_key()?;
+            user.require_not_impersonating()?;
+        }
+        Some(_) => return Err(AppError::forbidden("You can only create API keys for yourself")),
+        None => return Err(AppError::unauthorized("Unable to verify identity")),
+    }
+
+    if body.name.trim().is_empty() {
+        return Err(AppError::bad_request("API key name must not be empty"));
+    }
+
+    if let Some(unknown) = body.scopes.iter().find(|scope| ApiScope::parse(scope).is_none()) {
+        return Err(AppError::bad_request(format!(
+            "Unknown scope {}, expected one of {}",
+            unknown,
+            ApiScope::ALL.map(|scope| scope.as_str()).join(", ")
+        )));
+    }
+
+    if body.scopes.is_empty() {
+        return Err(AppError::bad_request("An API key needs at least one scope"));
+    }
+
+    let expires_at = match body.expires_in_days {
+        Some(days) if days > 0 => Some(Utc::now() + Duration::days(days)),
+        Some(_) => return Err(AppError::bad_request("expires_in_days must be positive")),
+        None => None,
+    };
+
+    let (prefix, key) = generate_api_key();
+
+    let api_key = sqlx::query_as::<_, ApiKey>(
+        "INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, name, prefix, scopes, created_at, last_used_at, expires_at, revoked_at",
+    )
+    .bind(user_id)
+    .bind(body.name.trim())
+    .bind(prefix)
+    .bind(hash_token(&key))
+    .bind(&body.scopes)
+    .bind(expires_at)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(HttpResponse::Created().json(CreatedApiKey { api_key, key }))
+}
+
+#[get("/user/{id}/api-keys")]
+async fn get_api_keys(
+    state: Data<AppState>,
+    auth: Authorized,
+    user_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+
+    auth.claims.require_not_api_key()?;
+
+    if !auth.owns_or_can(user_id, Permission::UserReadAny) {
+        return Err(AppError::forbidden("You can only see your own API keys"));
+    }
+
+    let api_keys = sqlx::query_as::<_, ApiKey>(
+        "SELECT id, name, prefix, scopes, created_at, last_used_at, expires_at, revoked_at
+        FROM api_keys WHERE user_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(api_keys))
+}
+
+#[delete("/user/{id}/api-keys/{key_id}")]
+async fn revoke_api_key(
+    state: Data<AppState>,
+    auth: Authorized,
+    path: Path<(i32, i32)>,
+) -> Result<HttpResponse, AppError> {
+    let (user_id, key_id) = path.into_inner();
+
+    auth.claims.require_not_api_key()?;
+    auth.claims.require_not_impersonating()?;
+
+    if !auth.owns_or_can(user_id, Permission::UserUpdateAny) {
+        return Err(AppError::forbidden("You can only revoke your own API keys"));
+    }
+
+    let api_key = sqlx::query_as::<_, ApiKey>(
+        "UPDATE api_keys SET revoked_at = COALESCE(revoked_at, NOW())
+        WHERE id = $1 AND user_id = $2
+        RETURNING id, name, prefix, scopes, created_at, last_used_at, expires_at, revoked_at",
+    )
+    .bind(key_id)
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::not_found("API key not found"))?;
+
+    Ok(HttpResponse::Ok().json(api_key))
+}
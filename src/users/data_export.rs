@@ -0,0 +1,156 @@
+use std::io::{Cursor, Write};
+
+use crate::articles::models::Article;
+use crate::auth::permissions::{Authorized, Permission};
+use crate::error::AppError;
+use crate::users::models::{
+    ApiKey, ExportedAccount, ExportedIdentity, ExportedImpersonation, Session, UserExport,
+};
+use crate::AppState;
+use actix_web::{
+    get,
+    http::header::ContentDisposition,
+    web::{self, Data, Path},
+    HttpResponse,
+};
+use chrono::Utc;
+use sqlx::{Error as SqlxError, PgPool};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+#[get("/user/{id}/export")]
+async fn export_user_data(
+    state: Data<AppState>,
+    auth: Authorized,
+    user_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+
+    if !auth.owns_or_can(user_id, Permission::UserReadAny) {
+        return Err(AppError::forbidden("You can only export your own data"));
+    }
+
+    let export = collect_user_data(&state.db, user_id, auth.claims.sid)
+        .await
+        .map_err(|error| match error {
+            SqlxError::RowNotFound => AppError::not_found("User not found"),
+            error => error.into(),
+        })?;
+
+    let archive = web::block(move || build_archive(&export))
+        .await?
+        .map_err(AppError::internal)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header(ContentDisposition::attachment(format!("user-{}-export.zip", user_id)))
+        .body(archive))
+}
+
+/// Everything stored about the user, apart from secrets such as password
+/// hashes, token hashes and the TOTP secret.
+async fn collect_user_data(
+    db: &PgPool,
+    user_id: i32,
+    current_session: Option<i32>,
+) -> Result<UserExport, sqlx::Error> {
+    let account = sqlx::query_as::<_, ExportedAccount>(
+        "SELECT id, username, email, role, verified_at, totp_enabled_at AS two_factor_enabled_at,
+                status, suspended_until, status_reason
+        FROM users WHERE id = $1",
+    )
+    .bind(user_id)
+    .fetch_one(db)
+    .await?;
+
+    let identities = sqlx::query_as::<_, ExportedIdentity>(
+        "SELECT provider, subject, email, created_at FROM user_identities WHERE user_id = $1 ORDER BY created_at",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    let sessions = sqlx::query_as::<_, Session>(
+        "SELECT id, device, user_agent, ip, created_at, last_seen_at, id IS NOT DISTINCT FROM $2 AS current
+        FROM sessions WHERE user_id = $1 ORDER BY created_at",
+    )
+    .bind(user_id)
+    .bind(current_session)
+    .fetch_all(db)
+    .await?;
+
+    let api_keys = sqlx::query_as::<_, ApiKey>(
+        "SELECT id, name, prefix, scopes, created_at, last_used_at, expires_at, revoked_at
+        FROM api_keys WHERE user_id = $1 ORDER BY created_at",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    let articles = sqlx::query_as::<_, Article>(
+        "SELECT id, title, content, published_by, published_on, status, review_comment,
+            publish_at, embargo_until
+        FROM articles WHERE published_by = $1 ORDER BY id",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    let impersonations = sqlx::query_as::<_, ExportedImpersonation>(
+        "SELECT admin_id, method, path, allowed, created_at FROM impersonation_audit_log
+        WHERE user_id = $1 ORDER BY created_at",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(UserExport {
+        exported_at: Utc::now(),
+        account,
+        identities,
+        sessions,
+        api_keys,
+        articles,
+        impersonations,
+    })
+}
+
+/// A zip with `user.json` holding the whole export and one Markdown file per article.
+fn build_archive(export: &UserExport) -> zip::result::ZipResult<Vec<u8>> {
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    zip.start_file("user.json", options)?;
+    serde_json::to_writer_pretty(&mut zip, export).map_err(std::io::Error::from)?;
+
+    for article in &export.articles {
+        zip.start_file(format!("articles/{}-{}.md", article.id, slug(&article.title)), options)?;
+        write!(
+            zip,
+            "---\nid: {}\ntitle: {}\nstatus: {}\npublished_on: {}\n---\n\n{}\n",
+            article.id,
+            // A JSON string is valid YAML, whatever the title contains.
+            serde_json::to_string(&article.title).unwrap_or_default(),
+            article.status.as_str(),
+            article.published_on.to_rfc3339(),
+            article.content
+        )?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+fn slug(title: &str) -> String {
+    let slug = title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    if slug.is_empty() {
+        "article".to_string()
+    } else {
+        slug.chars().take(60).collect()
+    }
+}
@@ -0,0 +1,121 @@
+use crate::auth::tokens::{generate_opaque_token, hash_token};
+use crate::error::AppError;
+use crate::mail::Email;
+use crate::users::models::UserNoPassword;
+use crate::{AppState, TokenClaims};
+use actix_web::{
+    get, post,
+    web::{Data, Path, ReqData},
+    HttpResponse,
+};
+use chrono::{Duration, Utc};
+
+const EMAIL_VERIFICATION_TTL_HOURS: i64 = 48;
+
+/// Stores a fresh confirmation token for `email` and mails the link to that
+/// address. Earlier unconfirmed tokens for the user stop working.
+pub async fn send_verification_email(
+    state: &AppState,
+    user_id: i32,
+    email: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE email_verification_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL",
+    )
+    .bind(user_id)
+    .execute(&state.db)
+    .await?;
+
+    let token = generate_opaque_token();
+
+    sqlx::query(
+        "INSERT INTO email_verification_tokens (user_id, email, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4)",
+    )
+    .bind(user_id)
+    .bind(email)
+    .bind(hash_token(&token))
+    .bind(Utc::now() + Duration::hours(EMAIL_VERIFICATION_TTL_HOURS))
+    .execute(&state.db)
+    .await?;
+
+    let message = Email {
+        to: email.to_string(),
+        subject: "Confirm your email address".to_string(),
+        body: format!(
+            "Please confirm this email address by opening the link below within {} hours:\n\n{}/verify-email/{}",
+            EMAIL_VERIFICATION_TTL_HOURS,
+            state.config.server.base_url,
+            token
+        ),
+    };
+
+    if let Err(error) = state.mailer.send(message).await {
+        eprintln!("{}", error);
+    }
+
+    Ok(())
+}
+
+pub async fn is_verified(state: &AppState, user_id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>("SELECT verified_at IS NOT NULL FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&state.db)
+        .await
+}
+
+#[get("/verify-email/{token}")]
+async fn verify_email(state: Data<AppState>, token: Path<String>) -> Result<HttpResponse, AppError> {
+    let token = token.into_inner();
+
+    let (user_id, email) = sqlx::query_as::<_, (i32, String)>(
+        "UPDATE email_verification_tokens SET used_at = NOW()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING user_id, email",
+    )
+    .bind(hash_token(&token))
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::bad_request("Invalid or expired verification link"))?;
+
+    let user = sqlx::query_as::<_, UserNoPassword>(
+        "UPDATE users SET email = $1, verified_at = NOW() WHERE id = $2
+        RETURNING id, username, email, role",
+    )
+    .bind(email)
+    .bind(user_id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|error| match error {
+        sqlx::Error::RowNotFound => AppError::not_found("User not found"),
+        sqlx::Error::Database(error) if error.is_unique_violation() => AppError::conflict("Email already exists"),
+        error => error.into(),
+    })?;
+
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[post("/verify-email/resend")]
+async fn resend_verification_email(
+    state: Data<AppState>,
+    req_user: Option<ReqData<TokenClaims>>,
+) -> Result<HttpResponse, AppError> {
+    let user = req_user.ok_or_else(|| AppError::unauthorized("Unable to verify identity"))?;
+    user.require_not_impersonating()?;
+
+    let (email, verified) = sqlx::query_as::<_, (String, bool)>(
+        "SELECT email, verified_at IS NOT NULL FROM users WHERE id = $1",
+    )
+    .bind(user.id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::not_found("User not found"))?;
+
+    if verified {
+        return Err(AppError::bad_request("Email is already verified"));
+    }
+
+    send_verification_email(&state, user.id, &email).await?;
+
+    Ok(HttpResponse::Ok().json("Verification email sent"))
+}
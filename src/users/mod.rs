@@ -1,4 +1,26 @@
+#[allow(clippy::module_inception)]
 pub mod users;
+pub mod account_deletion;
+pub mod account_status;
+pub mod api_keys;
+pub mod data_export;
+pub mod email_verification;
 pub mod models;
+pub mod password_policy;
+pub mod password_reset;
+pub mod sessions;
+pub mod two_factor;
 
-pub use users::{register,login,update_email,update_password,update_username};
+pub use users::{register,login,legacy_login,unlock_user,update_email,update_password,update_username};
+pub use password_policy::PasswordPolicy;
+pub use password_reset::{forgot_password, reset_password};
+pub use email_verification::{resend_verification_email, verify_email};
+pub use two_factor::{
+    confirm_two_factor, login_two_factor, login_two_factor_confirm, login_two_factor_setup,
+    set_role_mfa_policy, setup_two_factor,
+};
+pub use account_deletion::delete_user;
+pub use account_status::{ban_user, reactivate_user, suspend_user};
+pub use api_keys::{create_api_key, get_api_keys, revoke_api_key};
+pub use data_export::export_user_data;
+pub use sessions::{get_sessions, revoke_user_session};
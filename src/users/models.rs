@@ -1,11 +1,31 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{self, FromRow};
 
+use crate::articles::models::Article;
+use crate::auth::models::TokenResponse;
+
 #[derive(Deserialize)]
 pub struct CreateUserBody {
     pub username: String,
     pub password: String,
     pub email:String,
+    /// Answer with a login response instead of just the new user.
+    #[serde(default)]
+    pub login: bool,
+}
+
+#[derive(Deserialize)]
+pub struct LoginBody {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Serialize)]
+pub struct LoginResponse {
+    #[serde(flatten)]
+    pub tokens: TokenResponse,
+    pub user: UserNoPassword,
 }
 
 #[derive(Serialize, FromRow)]
@@ -22,6 +42,7 @@ pub struct AuthUser {
     pub id: i32,
     pub username: String,
     pub password: String,
+    pub email: String,
     pub role:String,
 }
 
@@ -30,7 +51,186 @@ pub struct UpdateUserBody {
     pub username: Option<String>,
     pub email: Option<String>,
     pub password: Option<String>,
+    /// Required when users change their own password.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_password: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordBody {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordBody {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Serialize)]
+pub struct TwoFactorSetupResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorCodeBody {
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct MfaChallengeResponse {
+    pub status: String,
+    pub mfa_token: String,
+    pub expires_in: i64,
+}
+
+#[derive(Deserialize)]
+pub struct MfaTokenBody {
+    pub mfa_token: String,
 }
 
+#[derive(Deserialize)]
+pub struct MfaLoginBody {
+    pub mfa_token: String,
+    pub code: String,
+}
 
+#[derive(Serialize)]
+pub struct MfaEnrollmentResponse {
+    pub recovery_codes: Vec<String>,
+    #[serde(flatten)]
+    pub login: LoginResponse,
+}
+
+#[derive(Deserialize)]
+pub struct RoleMfaPolicyBody {
+    pub required: bool,
+}
+
+#[derive(Deserialize)]
+pub struct CreateApiKeyBody {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_in_days: Option<i64>,
+}
 
+#[derive(Serialize, FromRow)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Session {
+    pub id: i32,
+    pub device: String,
+    pub user_agent: String,
+    pub ip: String,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    /// Whether this is the session making the request.
+    pub current: bool,
+}
+
+#[derive(Serialize, FromRow, Debug)]
+pub struct AccountStatus {
+    pub status: String,
+    pub suspended_until: Option<DateTime<Utc>>,
+    pub status_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SuspendUserBody {
+    pub until: DateTime<Utc>,
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+pub struct BanUserBody {
+    pub reason: String,
+    /// Hides the user's articles from the article list while the ban lasts.
+    #[serde(default)]
+    pub hide_articles: bool,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArticlePolicy {
+    /// Keep the articles under the shared "deleted-user" account.
+    #[default]
+    Anonymize,
+    Reassign,
+    Delete,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DeleteUserBody {
+    #[serde(default)]
+    pub articles: ArticlePolicy,
+    /// The new author when `articles` is "reassign".
+    pub reassign_to: Option<i32>,
+    /// Required when users delete their own account.
+    pub current_password: Option<String>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct ExportedAccount {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub two_factor_enabled_at: Option<DateTime<Utc>>,
+    pub status: String,
+    pub suspended_until: Option<DateTime<Utc>>,
+    pub status_reason: Option<String>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct ExportedIdentity {
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct ExportedImpersonation {
+    /// Empty once the admin's account was deleted.
+    pub admin_id: Option<i32>,
+    pub method: String,
+    pub path: String,
+    pub allowed: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct UserExport {
+    pub exported_at: DateTime<Utc>,
+    pub account: ExportedAccount,
+    pub identities: Vec<ExportedIdentity>,
+    pub sessions: Vec<Session>,
+    pub api_keys: Vec<ApiKey>,
+    pub articles: Vec<Article>,
+    pub impersonations: Vec<ExportedImpersonation>,
+}
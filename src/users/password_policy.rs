@@ -0,0 +1,223 @@
+use actix_web::web;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::path::PathBuf;
+
+use crate::config::PasswordConfig;
+
+#[derive(Serialize, Debug)]
+pub struct PolicyViolation {
+    pub rule: &'static str,
+    pub message: String,
+}
+
+pub struct PasswordPolicy {
+    min_length: usize,
+    min_entropy_bits: f64,
+    /// Directory of range files named after the first five hex characters of a
+    /// SHA-1 hash, each line holding the remaining 35 characters and a count,
+    /// the same layout as the Pwned Passwords range API.
+    breached_dir: Option<PathBuf>,
+}
+
+impl PasswordPolicy {
+    /// The breached check is skipped without `breached_passwords_dir`.
+    pub fn from_config(config: &PasswordConfig) -> Self {
+        PasswordPolicy {
+            min_length: config.min_length,
+            min_entropy_bits: config.min_entropy_bits,
+            breached_dir: config.breached_passwords_dir.clone(),
+        }
+    }
+
+    pub async fn check(
+        &self,
+        password: &str,
+        username: &str,
+        email: &str,
+    ) -> Result<(), Vec<PolicyViolation>> {
+        let mut violations = Vec::new();
+
+        if password.chars().count() < self.min_length {
+            violations.push(PolicyViolation {
+                rule: "min_length",
+                message: format!("Password must be at least {} characters long", self.min_length),
+            });
+        }
+
+        if estimate_entropy_bits(password) < self.min_entropy_bits {
+            violations.push(PolicyViolation {
+                rule: "strength",
+                message: "Password is too easy to guess, make it longer or mix in other kinds of characters"
+                    .to_string(),
+            });
+        }
+
+        if contains_personal_info(password, username, email) {
+            violations.push(PolicyViolation {
+                rule: "personal_info",
+                message: "Password must not contain your username or email".to_string(),
+            });
+        }
+
+        if self.is_breached(password).await {
+            violations.push(PolicyViolation {
+                rule: "breached",
+                message: "Password appears in a known data breach, choose another one".to_string(),
+            });
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    async fn is_breached(&self, password: &str) -> bool {
+        let Some(dir) = &self.breached_dir else {
+            return false;
+        };
+
+        let digest = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = digest.split_at(5);
+        let path = dir.join(format!("{}.txt", prefix));
+        let suffix = suffix.to_string();
+
+        let result = web::block(move || match std::fs::read_to_string(&path) {
+            Ok(contents) => Ok(contents.lines().any(|line| {
+                line.split(':')
+                    .next()
+                    .is_some_and(|hash| hash.trim().eq_ignore_ascii_case(&suffix))
+            })),
+            // A prefix without a file has no breached passwords.
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(error.to_string()),
+        })
+        .await
+        .map_err(|error| error.to_string())
+        .and_then(|found| found);
+
+        result.unwrap_or_else(|error| {
+            eprintln!("Failed to read breached password list: {}", error);
+            false
+        })
+    }
+}
+
+/// Length times the bits per character of the character classes in use,
+/// with repeated characters only counting half.
+fn estimate_entropy_bits(password: &str) -> f64 {
+    let mut pool = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password.chars().any(|c| c.is_ascii_punctuation() || c == ' ') {
+        pool += 33;
+    }
+    if !password.is_ascii() {
+        pool += 100;
+    }
+    if pool == 0 {
+        return 0.0;
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    let effective_length: f64 = password
+        .chars()
+        .map(|c| if seen.insert(c) { 1.0 } else { 0.5 })
+        .sum();
+
+    effective_length * (pool as f64).log2()
+}
+
+fn contains_personal_info(password: &str, username: &str, email: &str) -> bool {
+    let password = password.to_lowercase();
+    let local_part = email.split('@').next().unwrap_or_default();
+
+    [username, email, local_part]
+        .iter()
+        .map(|value| value.trim().to_lowercase())
+        .any(|value| value.chars().count() >= 3 && password.contains(&value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(min_entropy_bits: f64, breached_dir: Option<PathBuf>) -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 1,
+            min_entropy_bits,
+            breached_dir,
+        }
+    }
+
+    fn rules(result: Result<(), Vec<PolicyViolation>>) -> Vec<&'static str> {
+        result.err().unwrap_or_default().iter().map(|violation| violation.rule).collect()
+    }
+
+    #[test]
+    fn entropy_grows_with_the_character_classes_and_halves_repeats() {
+        let lowercase = 26f64.log2();
+        let all_classes = (26.0 + 26.0 + 10.0 + 33.0f64).log2();
+
+        assert_eq!(estimate_entropy_bits(""), 0.0);
+        assert_eq!(estimate_entropy_bits("abcd"), 4.0 * lowercase);
+        assert_eq!(estimate_entropy_bits("aaaa"), 2.5 * lowercase);
+        assert_eq!(estimate_entropy_bits("aB3!"), 4.0 * all_classes);
+        assert_eq!(estimate_entropy_bits("é"), 100f64.log2());
+    }
+
+    #[actix_web::test]
+    async fn a_password_exactly_at_the_threshold_passes() {
+        let password = "correcthorse";
+        let bits = estimate_entropy_bits(password);
+
+        assert!(rules(policy(bits, None).check(password, "alice", "alice@example.com").await).is_empty());
+        assert_eq!(
+            rules(policy(bits + 0.001, None).check(password, "alice", "alice@example.com").await),
+            ["strength"]
+        );
+    }
+
+    #[test]
+    fn finds_the_username_and_email_in_any_case() {
+        let email = "Alice.Smith@Example.com";
+
+        assert!(contains_personal_info("xxALICExx", "alice", email));
+        assert!(contains_personal_info("xxalice.smithxx", "bob", email));
+        assert!(contains_personal_info("ALICE.SMITH@EXAMPLE.COMxx", "bob", email));
+        assert!(!contains_personal_info("xxalicexx", "bob", "bob@example.com"));
+        // Names shorter than three characters would rule out too much.
+        assert!(!contains_personal_info("xxboxx", "bo", "bo@example.com"));
+    }
+
+    #[actix_web::test]
+    async fn looks_up_breached_passwords_in_the_range_files() {
+        // SHA-1 of "password" is 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8.
+        let dir = std::env::temp_dir().join(format!("breached-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("5BAA6.txt"),
+            "003D68EB55068C33ACE09247EE4C639306B:3\n1e4c9b93f3f0682250b6cf8331b7ee68fd8:9545824\n",
+        )
+        .unwrap();
+        // The range of "password-not-in-the-list", without its own suffix.
+        std::fs::write(dir.join("B605D.txt"), "0018A45C4D1DEF81644B54AB7F969B88D65:1\n").unwrap();
+        let policy = policy(0.0, Some(dir.clone()));
+
+        assert!(policy.is_breached("password").await);
+        assert!(!policy.is_breached("password-not-in-the-list").await);
+        // No range file for its prefix at all.
+        assert!(!policy.is_breached("Correct-Horse-Battery-9").await);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
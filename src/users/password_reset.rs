@@ -0,0 +1,118 @@
+use crate::auth::revocation::{revoke_all_tokens, revoke_api_keys};
+use crate::auth::tokens::{generate_opaque_token, hash_token};
+use crate::error::AppError;
+use crate::mail::Email;
+use crate::users::models::{ForgotPasswordBody, ResetPasswordBody};
+use crate::AppState;
+use actix_web::{
+    post,
+    web::{Data, Json},
+    HttpResponse,
+};
+use chrono::{Duration, Utc};
+
+#[post("/password/forgot")]
+async fn forgot_password(state: Data<AppState>, body: Json<ForgotPasswordBody>) -> Result<HttpResponse, AppError> {
+    let email = body.into_inner().email;
+
+    let user_id = sqlx::query_scalar::<_, i32>("SELECT id FROM users WHERE email = $1")
+        .bind(&email)
+        .fetch_optional(&state.db)
+        .await?;
+
+    // Same answer, and just as fast, whether or not the address is known, so this
+    // can't be used to find out who has an account. The link goes out afterwards.
+    if let Some(user_id) = user_id {
+        actix::spawn(async move {
+            if let Err(error) = send_reset_link(&state, user_id, email).await {
+                eprintln!("Failed to send password reset link: {:?}", error);
+            }
+        });
+    }
+
+    Ok(HttpResponse::Ok().json("If that email is registered, a reset link has been sent"))
+}
+
+async fn send_reset_link(state: &AppState, user_id: i32, email: String) -> Result<(), AppError> {
+    // Only the most recent link stays usable.
+    sqlx::query(
+        "UPDATE password_reset_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL",
+    )
+    .bind(user_id)
+    .execute(&state.db)
+    .await?;
+
+    let token = generate_opaque_token();
+    let ttl = Duration::minutes(state.config.tokens.password_reset_ttl_minutes);
+
+    sqlx::query(
+        "INSERT INTO password_reset_tokens (user_id, token_hash, expires_at)
+        VALUES ($1, $2, $3)",
+    )
+    .bind(user_id)
+    .bind(hash_token(&token))
+    .bind(Utc::now() + ttl)
+    .execute(&state.db)
+    .await?;
+
+    let email = Email {
+        to: email,
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Someone asked to reset the password for your account.\n\n\
+            Use this token with POST /password/reset within {} minutes:\n\n{}\n\n\
+            If it wasn't you, you can ignore this email.",
+            ttl.num_minutes(),
+            token
+        ),
+    };
+
+    state.mailer.send(email).await?;
+
+    Ok(())
+}
+
+#[post("/password/reset")]
+async fn reset_password(state: Data<AppState>, body: Json<ResetPasswordBody>) -> Result<HttpResponse, AppError> {
+    let body = body.into_inner();
+
+    // Checked before the token is used up, so a rejected password can be retried with the same link.
+    let (username, email) = sqlx::query_as::<_, (String, String)>(
+        "SELECT users.username, users.email FROM password_reset_tokens
+        JOIN users ON users.id = password_reset_tokens.user_id
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()",
+    )
+    .bind(hash_token(&body.token))
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::bad_request("Invalid or expired reset token"))?;
+
+    state
+        .password_policy
+        .check(&body.password, &username, &email)
+        .await
+        .map_err(AppError::PasswordPolicy)?;
+
+    let user_id = sqlx::query_scalar::<_, i32>(
+        "UPDATE password_reset_tokens SET used_at = NOW()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING user_id",
+    )
+    .bind(hash_token(&body.token))
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::bad_request("Invalid or expired reset token"))?;
+
+    let hashed_password = state.hasher.hash_async(&body.password).await?;
+
+    sqlx::query("UPDATE users SET password = $1 WHERE id = $2")
+        .bind(hashed_password)
+        .bind(user_id)
+        .execute(&state.db)
+        .await?;
+
+    revoke_all_tokens(&state, user_id).await?;
+    revoke_api_keys(&state.db, user_id).await?;
+
+    Ok(HttpResponse::Ok().json("Password has been reset"))
+}
@@ -0,0 +1,60 @@
+use crate::auth::permissions::{Authorized, Permission};
+use crate::auth::sessions::revoke_session;
+use crate::error::AppError;
+use crate::users::models::Session;
+use crate::AppState;
+use actix_web::{
+    delete, get,
+    web::{Data, Path},
+    HttpResponse,
+};
+
+#[get("/user/{id}/sessions")]
+async fn get_sessions(
+    state: Data<AppState>,
+    auth: Authorized,
+    user_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+
+    auth.claims.require_not_api_key()?;
+
+    if !auth.owns_or_can(user_id, Permission::UserReadAny) {
+        return Err(AppError::forbidden("You can only see your own sessions"));
+    }
+
+    let sessions = sqlx::query_as::<_, Session>(
+        "SELECT id, device, user_agent, ip, created_at, last_seen_at, id IS NOT DISTINCT FROM $2 AS current
+        FROM sessions
+        WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        ORDER BY last_seen_at DESC",
+    )
+    .bind(user_id)
+    .bind(auth.claims.sid)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+#[delete("/user/{id}/sessions/{sid}")]
+async fn revoke_user_session(
+    state: Data<AppState>,
+    auth: Authorized,
+    path: Path<(i32, i32)>,
+) -> Result<HttpResponse, AppError> {
+    let (user_id, session_id) = path.into_inner();
+
+    auth.claims.require_not_api_key()?;
+    auth.claims.require_not_impersonating()?;
+
+    if !auth.owns_or_can(user_id, Permission::UserUpdateAny) {
+        return Err(AppError::forbidden("You can only end your own sessions"));
+    }
+
+    if !revoke_session(&state.db, user_id, session_id).await? {
+        return Err(AppError::not_found("Session not found"));
+    }
+
+    Ok(HttpResponse::Ok().json("Session revoked"))
+}
@@ -0,0 +1,377 @@
+use crate::auth::lockout::{account_key, ip_key, locked_for, record_failure, record_success};
+use crate::auth::permissions::{Authorized, Permission};
+use crate::auth::sessions::ClientInfo;
+use crate::auth::tokens::{generate_opaque_token, hash_token, issue_session};
+use crate::auth::totp::{
+    generate_recovery_codes, generate_secret, normalize_recovery_code, otpauth_uri, verify_code,
+};
+use crate::error::AppError;
+use crate::users::account_status::account_block;
+use crate::users::models::{
+    LoginResponse, MfaChallengeResponse, MfaEnrollmentResponse, MfaLoginBody, MfaTokenBody,
+    RecoveryCodesResponse, RoleMfaPolicyBody, TwoFactorCodeBody, TwoFactorSetupResponse,
+    UserNoPassword,
+};
+use crate::{AppState, TokenClaims};
+use actix_web::{
+    post, put,
+    web::{Data, Json, Path, ReqData},
+    HttpRequest, HttpResponse,
+};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+const MFA_CHALLENGE_TTL_MINUTES: i64 = 5;
+const MFA_CHALLENGE_MAX_ATTEMPTS: i32 = 5;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Decides whether a login that passed the password check still needs a
+/// second step: a code when 2FA is on, enrollment when the role requires it.
+pub async fn mfa_challenge_for(
+    db: &PgPool,
+    user_id: i32,
+) -> Result<Option<MfaChallengeResponse>, sqlx::Error> {
+    let (enabled, required) = sqlx::query_as::<_, (bool, bool)>(
+        "SELECT totp_enabled_at IS NOT NULL,
+                EXISTS (SELECT 1 FROM mfa_required_roles WHERE mfa_required_roles.role = users.role)
+        FROM users WHERE id = $1",
+    )
+    .bind(user_id)
+    .fetch_one(db)
+    .await?;
+
+    let (purpose, status) = match (enabled, required) {
+        (true, _) => ("verify", "mfa_required"),
+        (false, true) => ("enroll", "mfa_enrollment_required"),
+        (false, false) => return Ok(None),
+    };
+
+    let mfa_token = generate_opaque_token();
+
+    sqlx::query(
+        "INSERT INTO mfa_challenges (user_id, token_hash, purpose, expires_at)
+        VALUES ($1, $2, $3, $4)",
+    )
+    .bind(user_id)
+    .bind(hash_token(&mfa_token))
+    .bind(purpose)
+    .bind(Utc::now() + Duration::minutes(MFA_CHALLENGE_TTL_MINUTES))
+    .execute(db)
+    .await?;
+
+    Ok(Some(MfaChallengeResponse {
+        status: status.to_string(),
+        mfa_token,
+        expires_in: MFA_CHALLENGE_TTL_MINUTES * 60,
+    }))
+}
+
+/// Counts an attempt against the challenge and returns its user while it is
+/// still valid.
+async fn challenge_user(
+    db: &PgPool,
+    mfa_token: &str,
+    purpose: &str,
+) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>(
+        "UPDATE mfa_challenges SET attempts = attempts + 1
+        WHERE token_hash = $1 AND purpose = $2 AND expires_at > NOW() AND attempts < $3
+        RETURNING user_id",
+    )
+    .bind(hash_token(mfa_token))
+    .bind(purpose)
+    .bind(MFA_CHALLENGE_MAX_ATTEMPTS)
+    .fetch_optional(db)
+    .await
+}
+
+async fn finish_challenge(db: &PgPool, mfa_token: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM mfa_challenges WHERE token_hash = $1")
+        .bind(hash_token(mfa_token))
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Accepts either a current TOTP code or an unused recovery code.
+async fn verify_second_factor(db: &PgPool, user_id: i32, code: &str) -> Result<bool, sqlx::Error> {
+    let (secret, last_used_step) = sqlx::query_as::<_, (Option<String>, Option<i64>)>(
+        "SELECT totp_secret, totp_last_used_step FROM users
+        WHERE id = $1 AND totp_enabled_at IS NOT NULL",
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?
+    .unwrap_or((None, None));
+
+    if let Some(step) = secret.and_then(|secret| verify_code(&secret, code, last_used_step)) {
+        let accepted = sqlx::query(
+            "UPDATE users SET totp_last_used_step = $1
+            WHERE id = $2 AND (totp_last_used_step IS NULL OR totp_last_used_step < $1)",
+        )
+        .bind(step)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+        return Ok(accepted.rows_affected() == 1);
+    }
+
+    let recovered = sqlx::query(
+        "UPDATE recovery_codes SET used_at = NOW()
+        WHERE id = (SELECT id FROM recovery_codes
+                    WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+                    LIMIT 1)",
+    )
+    .bind(user_id)
+    .bind(hash_token(&normalize_recovery_code(code)))
+    .execute(db)
+    .await?;
+
+    Ok(recovered.rows_affected() == 1)
+}
+
+async fn begin_enrollment(state: &AppState, user_id: i32) -> Result<TwoFactorSetupResponse, AppError> {
+    let secret = generate_secret();
+
+    let username = sqlx::query_scalar::<_, String>(
+        "UPDATE users SET totp_secret = $1, totp_last_used_step = NULL
+        WHERE id = $2 AND totp_enabled_at IS NULL
+        RETURNING username",
+    )
+    .bind(&secret)
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| {
+        AppError::conflict("Two-factor authentication is already enabled or the user does not exist")
+    })?;
+
+    Ok(TwoFactorSetupResponse {
+        otpauth_uri: otpauth_uri(&state.config.two_factor.issuer, &username, &secret),
+        secret,
+    })
+}
+
+async fn confirm_enrollment(db: &PgPool, user_id: i32, code: &str) -> Result<Vec<String>, AppError> {
+    let secret = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT totp_secret FROM users WHERE id = $1 AND totp_enabled_at IS NULL",
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?
+    .flatten()
+    .ok_or_else(|| AppError::conflict("Two-factor setup has not been started or is already enabled"))?;
+
+    let step = verify_code(&secret, code, None).ok_or_else(|| AppError::bad_request("Invalid code"))?;
+
+    let recovery_codes = generate_recovery_codes(RECOVERY_CODE_COUNT);
+    let code_hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|code| hash_token(&normalize_recovery_code(code)))
+        .collect();
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("UPDATE users SET totp_enabled_at = NOW(), totp_last_used_step = $1 WHERE id = $2")
+        .bind(step)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("INSERT INTO recovery_codes (user_id, code_hash) SELECT $1, UNNEST($2::TEXT[])")
+        .bind(user_id)
+        .bind(&code_hashes)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(recovery_codes)
+}
+
+/// Only the account owner may enroll their own authenticator, signed in for
+/// real: not through an API key or while impersonated.
+fn require_self(req_user: Option<ReqData<TokenClaims>>, user_id: i32) -> Result<(), AppError> {
+    match req_user {
+        Some(user) if user.id == user_id => {
+            user.require_not_api_key()?;
+            user.require_not_impersonating()
+        }
+        Some(_) => Err(AppError::forbidden(
+            "You can only set up two-factor authentication for yourself",
+        )),
+        None => Err(AppError::unauthorized("Unable to verify identity")),
+    }
+}
+
+#[post("/user/{id}/2fa/setup")]
+async fn setup_two_factor(
+    state: Data<AppState>,
+    req_user: Option<ReqData<TokenClaims>>,
+    user_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+    require_self(req_user, user_id)?;
+
+    let setup = begin_enrollment(&state, user_id).await?;
+
+    Ok(HttpResponse::Ok().json(setup))
+}
+
+#[post("/user/{id}/2fa/confirm")]
+async fn confirm_two_factor(
+    state: Data<AppState>,
+    req_user: Option<ReqData<TokenClaims>>,
+    user_id: Path<i32>,
+    body: Json<TwoFactorCodeBody>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = user_id.into_inner();
+    require_self(req_user, user_id)?;
+
+    let recovery_codes = confirm_enrollment(&state.db, user_id, &body.code).await?;
+
+    Ok(HttpResponse::Ok().json(RecoveryCodesResponse { recovery_codes }))
+}
+
+#[post("/login/2fa")]
+async fn login_two_factor(
+    state: Data<AppState>,
+    req: HttpRequest,
+    body: Json<MfaLoginBody>,
+) -> Result<HttpResponse, AppError> {
+    let body = body.into_inner();
+
+    let user_id = challenge_user(&state.db, &body.mfa_token, "verify")
+        .await?
+        .ok_or_else(|| AppError::unauthorized("Invalid or expired login challenge"))?;
+
+    let user = sqlx::query_as::<_, UserNoPassword>("SELECT id, username, email, role FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&state.db)
+        .await?;
+
+    // Wrong codes count towards the same lockout as wrong passwords, a fresh
+    // challenge from logging in again must not mean fresh guesses.
+    let client = ClientInfo::from_request(&req);
+    if let Some(retry_after) = locked_for(&state.db, &[account_key(&user.username), ip_key(&client.ip)]).await? {
+        return Err(AppError::TooManyRequests { retry_after });
+    }
+
+    if !verify_second_factor(&state.db, user_id, &body.code).await? {
+        record_failure(&state.db, &user.username, &client.ip).await?;
+        return Err(AppError::unauthorized("Invalid code"));
+    }
+
+    // The account may have been suspended since the password step.
+    if let Some(status) = account_block(&state.db, user_id).await? {
+        return Err(AppError::AccountBlocked(status));
+    }
+
+    finish_challenge(&state.db, &body.mfa_token).await?;
+    record_success(&state.db, &user.username).await?;
+
+    let tokens = issue_session(&state, user.id, user.role.clone(), &client).await?;
+
+    Ok(HttpResponse::Ok().json(LoginResponse { tokens, user }))
+}
+
+#[post("/login/2fa/setup")]
+async fn login_two_factor_setup(
+    state: Data<AppState>,
+    body: Json<MfaTokenBody>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = challenge_user(&state.db, &body.mfa_token, "enroll")
+        .await?
+        .ok_or_else(|| AppError::unauthorized("Invalid or expired login challenge"))?;
+
+    let setup = begin_enrollment(&state, user_id).await?;
+
+    Ok(HttpResponse::Ok().json(setup))
+}
+
+#[post("/login/2fa/confirm")]
+async fn login_two_factor_confirm(
+    state: Data<AppState>,
+    req: HttpRequest,
+    body: Json<MfaLoginBody>,
+) -> Result<HttpResponse, AppError> {
+    let body = body.into_inner();
+
+    let user_id = challenge_user(&state.db, &body.mfa_token, "enroll")
+        .await?
+        .ok_or_else(|| AppError::unauthorized("Invalid or expired login challenge"))?;
+
+    if let Some(status) = account_block(&state.db, user_id).await? {
+        return Err(AppError::AccountBlocked(status));
+    }
+
+    let recovery_codes = confirm_enrollment(&state.db, user_id, &body.code).await?;
+
+    finish_challenge(&state.db, &body.mfa_token).await?;
+
+    let user = sqlx::query_as::<_, UserNoPassword>("SELECT id, username, email, role FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&state.db)
+        .await?;
+
+    record_success(&state.db, &user.username).await?;
+
+    let tokens = issue_session(&state, user.id, user.role.clone(), &ClientInfo::from_request(&req)).await?;
+
+    Ok(HttpResponse::Ok().json(MfaEnrollmentResponse {
+        recovery_codes,
+        login: LoginResponse { tokens, user },
+    }))
+}
+
+#[put("/admin/2fa/roles/{role}")]
+async fn set_role_mfa_policy(
+    state: Data<AppState>,
+    auth: Authorized,
+    role: Path<String>,
+    body: Json<RoleMfaPolicyBody>,
+) -> Result<HttpResponse, AppError> {
+    let role = role.into_inner();
+
+    auth.require(Permission::MfaPolicyManage)?;
+
+    if body.required {
+        // Don't let the caller lock their own role out before they enrolled.
+        if auth.claims.role == role {
+            let enrolled = sqlx::query_scalar::<_, bool>(
+                "SELECT totp_enabled_at IS NOT NULL FROM users WHERE id = $1",
+            )
+            .bind(auth.id())
+            .fetch_one(&state.db)
+            .await?;
+
+            if !enrolled {
+                return Err(AppError::conflict(
+                    "Enable two-factor authentication on your own account first",
+                ));
+            }
+        }
+
+        sqlx::query("INSERT INTO mfa_required_roles (role) VALUES ($1) ON CONFLICT (role) DO NOTHING")
+            .bind(&role)
+            .execute(&state.db)
+            .await?;
+    } else {
+        sqlx::query("DELETE FROM mfa_required_roles WHERE role = $1")
+            .bind(&role)
+            .execute(&state.db)
+            .await?;
+    }
+
+    Ok(HttpResponse::Ok().json(format!(
+        "Two-factor authentication is now {} for role {}",
+        if body.required { "required" } else { "optional" },
+        role
+    )))
+}
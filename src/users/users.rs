@@ -1,52 +1,73 @@
-use crate::users::models::{AuthUser, CreateUserBody, UpdateUserBody, UserNoPassword};
-use crate::{AppState, TokenClaims};
+use crate::auth::lockout::{account_key, ip_key, locked_for, record_failure, record_success};
+use crate::auth::permissions::{Authorized, Permission};
+use crate::auth::revocation::{revoke_access_tokens, revoke_all_tokens, revoke_api_keys};
+use crate::auth::sessions::{revoke_other_sessions, revoke_refresh_tokens, ClientInfo};
+use crate::auth::tokens::{issue_session, issue_token_pair};
+use crate::error::AppError;
+use crate::users::email_verification::send_verification_email;
+use crate::users::two_factor::mfa_challenge_for;
+use crate::mail::Email;
+use crate::users::account_deletion::is_reserved_username;
+use crate::users::account_status::account_block;
+use crate::users::models::{
+    AuthUser, CreateUserBody, LoginBody, LoginResponse, UpdateUserBody, UserNoPassword,
+};
+use crate::AppState;
 use actix_web::{
-    get, post, put, web,
-    web::{Data, Json, Path, ReqData},
-    HttpResponse, Responder,
+    get, http::header, post, put, web,
+    web::{Data, Json, Path},
+    HttpRequest, HttpResponse, ResponseError,
 };
 use actix_web_httpauth::extractors::basic::BasicAuth;
-use bcrypt::{hash, verify, DEFAULT_COST};
-use hmac::{Hmac, Mac};
-use jwt::SignWithKey;
 use regex::Regex;
-use sha2::Sha256;
 use sqlx::{self, Error as SqlxError};
 
 #[post("/register")]
-async fn register(state: Data<AppState>, body: Json<CreateUserBody>) -> impl Responder {
+async fn register(
+    state: Data<AppState>,
+    req: HttpRequest,
+    body: Json<CreateUserBody>,
+) -> Result<HttpResponse, AppError> {
     let user = body.into_inner();
 
     let email_regex = Regex::new(r"^[\w\.-]+@[\w\.-]+\.[a-zA-Z]{2,4}$").unwrap();
     if !email_regex.is_match(&user.email) {
-        return HttpResponse::BadRequest().json("Invalid email format");
+        return Err(AppError::bad_request("Invalid email format"));
     }
 
-    let hashed_password = hash(&user.password, DEFAULT_COST).unwrap();
+    state
+        .password_policy
+        .check(&user.password, &user.username, &user.email)
+        .await
+        .map_err(AppError::PasswordPolicy)?;
+
+    if is_reserved_username(&user.username) {
+        return Err(AppError::conflict("Username already exists"));
+    }
+
+    let hashed_password = state.hasher.hash_async(&user.password).await?;
 
     let username_exists =
         sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM users WHERE username = $1)")
             .bind(&user.username)
             .fetch_one(&state.db)
-            .await
-            .unwrap_or(false);
+            .await?;
 
     if username_exists {
-        return HttpResponse::Conflict().json("Username already exists");
+        return Err(AppError::conflict("Username already exists"));
     }
 
     let email_exists =
         sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM users WHERE email = $1)")
             .bind(&user.email)
             .fetch_one(&state.db)
-            .await
-            .unwrap_or(false);
+            .await?;
 
     if email_exists {
-        return HttpResponse::Conflict().json("Email already exists");
+        return Err(AppError::conflict("Email already exists"));
     }
 
-    match sqlx::query_as::<_, UserNoPassword>(
+    let created = sqlx::query_as::<_, UserNoPassword>(
         "INSERT INTO users (username, password, email, role)
         VALUES ($1, $2, $3, 'user')
         RETURNING id, username, email, role",
@@ -55,190 +76,366 @@ async fn register(state: Data<AppState>, body: Json<CreateUserBody>) -> impl Res
     .bind(hashed_password)
     .bind(user.email)
     .fetch_one(&state.db)
-    .await
-    {
-        Ok(user) => HttpResponse::Ok().json(user),
-        Err(error) => HttpResponse::InternalServerError().json(format!("{:?}", error)),
+    .await?;
+
+    send_verification_email(&state, created.id, &created.email).await?;
+
+    if user.login {
+        start_login(&state, &req, created).await
+    } else {
+        Ok(HttpResponse::Ok().json(created))
     }
 }
 
+#[post("/auth/login")]
+async fn login(
+    state: Data<AppState>,
+    req: HttpRequest,
+    body: Json<LoginBody>,
+) -> Result<HttpResponse, AppError> {
+    let body = body.into_inner();
+
+    authenticate(&state, &req, &body.username, &body.password).await
+}
+
+/// Deprecated, kept for older clients: the same as POST /auth/login with HTTP
+/// Basic credentials.
 #[get("/login")]
-async fn login(state: Data<AppState>, credentials: BasicAuth) -> impl Responder {
-    let jwt_secret: Hmac<Sha256> = Hmac::new_from_slice(
-        std::env::var("JWT_SECRET")
-            .expect("JWT_SECRET must be set!")
-            .as_bytes(),
+async fn legacy_login(state: Data<AppState>, req: HttpRequest, credentials: BasicAuth) -> HttpResponse {
+    let result = match credentials.password() {
+        Some(password) => authenticate(&state, &req, credentials.user_id(), password).await,
+        None => Err(AppError::unauthorized("Must provide a valid password")),
+    };
+
+    let mut response = result.unwrap_or_else(|error| error.error_response());
+
+    let headers = response.headers_mut();
+    headers.insert(
+        header::HeaderName::from_static("deprecation"),
+        header::HeaderValue::from_static("true"),
+    );
+    headers.insert(
+        header::LINK,
+        header::HeaderValue::from_static("</auth/login>; rel=\"successor-version\""),
+    );
+
+    response
+}
+
+/// Checks the password a signed-in user enters again to confirm a change. Wrong
+/// guesses count towards the same lockout as logins, a token alone must not
+/// allow unlimited tries.
+pub async fn verify_current_password(
+    state: &AppState,
+    req: &HttpRequest,
+    username: &str,
+    current: Option<&str>,
+    stored_hash: &str,
+) -> Result<(), AppError> {
+    let current = current.ok_or_else(|| AppError::bad_request("Current password not provided"))?;
+    let ip = req
+        .peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_default();
+
+    if let Some(retry_after) = locked_for(&state.db, &[account_key(username), ip_key(&ip)]).await? {
+        return Err(AppError::TooManyRequests { retry_after });
+    }
+
+    if !state.hasher.verify_async(current, stored_hash).await?.valid {
+        record_failure(&state.db, username, &ip).await?;
+        return Err(AppError::unauthorized("Current password is incorrect"));
+    }
+
+    Ok(())
+}
+
+async fn authenticate(
+    state: &AppState,
+    req: &HttpRequest,
+    username: &str,
+    password: &str,
+) -> Result<HttpResponse, AppError> {
+    let ip = req
+        .peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_default();
+
+    if let Some(retry_after) = locked_for(&state.db, &[account_key(username), ip_key(&ip)]).await? {
+        return Err(AppError::TooManyRequests { retry_after });
+    }
+
+    let user = sqlx::query_as::<_, AuthUser>(
+        "SELECT id, username, password, email, role FROM users WHERE username = $1",
     )
-    .unwrap();
-
-    let username = credentials.user_id();
-    let password = credentials.password();
-
-    match password {
-        None => HttpResponse::Unauthorized().json("Must provide a valid password"),
-        Some(pass) => {
-            match sqlx::query_as::<_, AuthUser>(
-                "SELECT id, username, password, role FROM users WHERE username = $1",
-            )
-            .bind(username.to_string())
-            .fetch_one(&state.db)
-            .await
-            {
-                Ok(user) => {
-                    let is_valid = verify(pass, &user.password).unwrap();
-                    if is_valid {
-                        let claims = TokenClaims {
-                            id: user.id,
-                            role: user.role,
-                        };
-
-                        let token = claims.sign_with_key(&jwt_secret).unwrap();
-                        HttpResponse::Ok().json(token)
-                    } else {
-                        HttpResponse::Unauthorized().json("Invalid credentials")
-                    }
-                }
-                Err(_) => HttpResponse::InternalServerError().json("Error fetching user"),
-            }
+    .bind(username)
+    .fetch_optional(&state.db)
+    .await?;
+
+    // Unknown usernames still pay for a full hash check so they answer as
+    // slowly as a wrong password does.
+    let check = match &user {
+        Some(user) => Some(state.hasher.verify_async(password, &user.password).await?),
+        None => {
+            state.hasher.verify_dummy(password).await?;
+            None
+        }
+    };
+
+    let (user, check) = match (user, check) {
+        (Some(user), Some(check)) if check.valid => (user, check),
+        _ => {
+            record_failure(&state.db, username, &ip).await?;
+            return Err(AppError::unauthorized("Invalid credentials"));
         }
+    };
+
+    // Only told after the password checked out, so it can't be used to probe accounts.
+    if let Some(status) = account_block(&state.db, user.id).await? {
+        return Err(AppError::AccountBlocked(status));
+    }
+
+    if check.needs_rehash {
+        sqlx::query("UPDATE users SET password = $1 WHERE id = $2")
+            .bind(state.hasher.hash_async(password).await?)
+            .bind(user.id)
+            .execute(&state.db)
+            .await?;
+    }
+
+    let user = UserNoPassword {
+        id: user.id,
+        username: user.username,
+        email: user.email,
+        role: user.role,
+    };
+
+    start_login(state, req, user).await
+}
+
+/// Hands out the tokens for a user whose credentials checked out, or the
+/// second-factor challenge when their role needs one first. The account's
+/// failed logins are only forgotten once no second factor is left to guess.
+pub async fn start_login(state: &AppState, req: &HttpRequest, user: UserNoPassword) -> Result<HttpResponse, AppError> {
+    if let Some(challenge) = mfa_challenge_for(&state.db, user.id).await? {
+        return Ok(HttpResponse::Ok().json(challenge));
     }
+
+    record_success(&state.db, &user.username).await?;
+
+    let tokens = issue_session(state, user.id, user.role.clone(), &ClientInfo::from_request(req)).await?;
+
+    Ok(HttpResponse::Ok().json(LoginResponse { tokens, user }))
+}
+
+#[post("/admin/users/{id}/unlock")]
+async fn unlock_user(
+    state: Data<AppState>,
+    auth: Authorized,
+    user_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::UserUnlock)?;
+
+    let username = sqlx::query_scalar::<_, String>("SELECT username FROM users WHERE id = $1")
+        .bind(user_id.into_inner())
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::not_found("User not found"))?;
+
+    record_success(&state.db, &username).await?;
+
+    Ok(HttpResponse::Ok().json("Account unlocked"))
 }
 
 #[put("/user/{id}/username")]
 async fn update_username(
     state: web::Data<AppState>,
-    req_user: Option<web::ReqData<TokenClaims>>,
+    auth: Authorized,
     user_id: web::Path<i32>,
     body: web::Json<UpdateUserBody>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let user_id = user_id.into_inner();
     let update_info = body.into_inner();
 
-    if let Some(user) = req_user.as_ref().map(|data| data.clone().into_inner())  {
-        if user.id != user_id && user.role != "admin" {
-            return HttpResponse::Forbidden()
-                .json("You can only update your own information or be an admin");
-        }
+    auth.claims.require_not_impersonating()?;
 
-        if let Some(username) = &update_info.username {
-            if let Ok(_) = sqlx::query("SELECT id FROM users WHERE username = $1")
-                .bind(username)
-                .fetch_one(&state.db)
-                .await
-            {
-                return HttpResponse::BadRequest().json("Username already exists");
-            }
+    if !auth.owns_or_can(user_id, Permission::UserUpdateAny) {
+        return Err(AppError::forbidden("You can only update your own information"));
+    }
 
-            let result = sqlx::query_as::<_, UserNoPassword>(
-                "UPDATE users SET username = $1 WHERE id = $2 RETURNING id, username, email, role",
-            )
-            .bind(username)
-            .bind(user_id)
+    let username = update_info
+        .username
+        .ok_or_else(|| AppError::bad_request("Username not provided"))?;
+
+    if is_reserved_username(&username) {
+        return Err(AppError::conflict("Username already exists"));
+    }
+
+    let username_exists =
+        sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM users WHERE username = $1)")
+            .bind(&username)
             .fetch_one(&state.db)
-            .await;
+            .await?;
 
-            match result {
-                Ok(updated_user) => HttpResponse::Ok().json(updated_user),
-                Err(sqlx::Error::RowNotFound) => HttpResponse::NotFound().json("User not found"),
-                Err(error) => HttpResponse::InternalServerError().json(format!("Database error: {:?}", error)),
-            }
-        } else {
-            HttpResponse::BadRequest().json("Username not provided")
-        }
-    } else {
-        HttpResponse::Unauthorized().json("Unable to verify identity")
+    if username_exists {
+        return Err(AppError::conflict("Username already exists"));
     }
+
+    let updated_user = sqlx::query_as::<_, UserNoPassword>(
+        "UPDATE users SET username = $1 WHERE id = $2 RETURNING id, username, email, role",
+    )
+    .bind(&username)
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::not_found("User not found"))?;
+
+    Ok(HttpResponse::Ok().json(updated_user))
 }
 
 #[put("/user/{id}/email")]
 async fn update_email(
     state: Data<AppState>,
-    req_user: Option<ReqData<TokenClaims>>,
+    auth: Authorized,
     user_id: Path<i32>,
     body: Json<UpdateUserBody>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let user_id = user_id.into_inner();
     let update_info = body.into_inner();
 
-    if let Some(user) = req_user.as_ref().map(|data| data.clone().into_inner()) {
-        if user.id != user_id && user.role != "admin" {
-            return HttpResponse::Forbidden()
-                .json("You can only update your own information or be an admin");
-        }
+    auth.claims.require_not_impersonating()?;
 
-        if let Some(email) = &update_info.email {
-            let email_regex = Regex::new(r"^[\w\.-]+@[\w\.-]+\.[a-zA-Z]{2,4}$").unwrap();
-            if !email_regex.is_match(email) {
-                return HttpResponse::BadRequest().json("Invalid email format");
-            }
+    if !auth.owns_or_can(user_id, Permission::UserUpdateAny) {
+        return Err(AppError::forbidden("You can only update your own information"));
+    }
 
-            if let Ok(_) = sqlx::query("SELECT id FROM users WHERE email = $1")
-                .bind(email)
-                .fetch_one(&state.db)
-                .await
-            {
-                return HttpResponse::BadRequest().json("Email already exists");
-            }
+    let email = update_info
+        .email
+        .ok_or_else(|| AppError::bad_request("Email not provided"))?;
 
-            let result = sqlx::query_as::<_, UserNoPassword>(
-                "UPDATE users SET email = $1 WHERE id = $2 RETURNING id, username, email, role",
-            )
-            .bind(email)
-            .bind(user_id)
+    let email_regex = Regex::new(r"^[\w\.-]+@[\w\.-]+\.[a-zA-Z]{2,4}$").unwrap();
+    if !email_regex.is_match(&email) {
+        return Err(AppError::bad_request("Invalid email format"));
+    }
+
+    let email_exists =
+        sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM users WHERE email = $1)")
+            .bind(&email)
             .fetch_one(&state.db)
-            .await;
+            .await?;
 
-            match result {
-                Ok(updated_user) => HttpResponse::Ok().json(updated_user),
-                Err(SqlxError::RowNotFound) => HttpResponse::NotFound().json("User not found"),
-                Err(error) => HttpResponse::InternalServerError().json(format!("Database error: {:?}", error)),
-            }
-        } else {
-            HttpResponse::BadRequest().json("Email not provided")
-        }
-    } else {
-        HttpResponse::Unauthorized().json("Unable to verify identity")
+    if email_exists {
+        return Err(AppError::conflict("Email already exists"));
     }
+
+    // The address only changes once the link sent to it is opened.
+    sqlx::query_scalar::<_, i32>("SELECT id FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::not_found("User not found"))?;
+
+    send_verification_email(&state, user_id, &email).await?;
+
+    Ok(HttpResponse::Accepted()
+        .json("Confirmation link sent to the new address, the email changes once it is opened"))
 }
 
 #[put("/user/{id}/password")]
 async fn update_password(
     state: Data<AppState>,
-    req_user: Option<ReqData<TokenClaims>>,
+    req: HttpRequest,
+    auth: Authorized,
     user_id: Path<i32>,
     body: Json<UpdateUserBody>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let user_id = user_id.into_inner();
     let update_info = body.into_inner();
 
-    if let Some(user) = req_user.as_ref().map(|data| data.clone().into_inner()) {
-        if user.id != user_id && user.role != "admin" {
-            return HttpResponse::Forbidden()
-                .json("You can only update your own information or be an admin");
-        }
+    auth.claims.require_not_impersonating()?;
 
-        if let Some(password) = update_info.password {
-            let hashed_password = hash(&password, DEFAULT_COST).unwrap();
+    if !auth.owns_or_can(user_id, Permission::UserUpdateAny) {
+        return Err(AppError::forbidden("You can only update your own information"));
+    }
 
-            let result = sqlx::query_as::<_, UserNoPassword>(
-                "UPDATE users SET password = $1 WHERE id = $2 RETURNING id, username, email, role",
-            )
-            .bind(hashed_password)
-            .bind(user_id)
-            .fetch_one(&state.db)
-            .await;
+    let password = update_info
+        .password
+        .ok_or_else(|| AppError::bad_request("Password not provided"))?;
+
+    let (username, email, current_hash) = sqlx::query_as::<_, (String, String, String)>(
+        "SELECT username, email, password FROM users WHERE id = $1",
+    )
+    .bind(user_id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|error| match error {
+        SqlxError::RowNotFound => AppError::not_found("User not found"),
+        error => error.into(),
+    })?;
+
+    // A stolen token alone must not be enough to take the account over.
+    let own_password = auth.id() == user_id;
+    if own_password {
+        verify_current_password(&state, &req, &username, update_info.current_password.as_deref(), &current_hash)
+            .await?;
+    }
 
-            match result {
-                Ok(updated_user) => HttpResponse::Ok().json(updated_user),
-                Err(SqlxError::RowNotFound) => HttpResponse::NotFound().json("User not found"),
-                Err(error) => HttpResponse::InternalServerError().json(format!("Database error: {:?}", error)),
+    state
+        .password_policy
+        .check(&password, &username, &email)
+        .await
+        .map_err(AppError::PasswordPolicy)?;
+
+    let hashed_password = state.hasher.hash_async(&password).await?;
+
+    let updated_user = sqlx::query_as::<_, UserNoPassword>(
+        "UPDATE users SET password = $1 WHERE id = $2 RETURNING id, username, email, role",
+    )
+    .bind(hashed_password)
+    .bind(user_id)
+    .fetch_one(&state.db)
+    .await?;
+
+    // Users keep the session they changed it from, everywhere else is logged out.
+    // Every access token goes, those without a session too, so the session they
+    // kept gets a fresh pair.
+    let tokens = if own_password {
+        revoke_other_sessions(&state.db, user_id, auth.claims.sid).await?;
+        revoke_access_tokens(&state, user_id).await?;
+        let role = updated_user.role.clone();
+        Some(match auth.claims.sid {
+            Some(session_id) => {
+                revoke_refresh_tokens(&state.db, session_id).await?;
+                issue_token_pair(&state, user_id, role, session_id).await?
             }
-        } else {
-            HttpResponse::BadRequest().json("Password not provided")
-        }
+            None => issue_session(&state, user_id, role, &ClientInfo::from_request(&req)).await?,
+        })
     } else {
-        HttpResponse::Unauthorized().json("Unable to verify identity")
-    }
-}
+        revoke_all_tokens(&state, user_id).await?;
+        None
+    };
+    revoke_api_keys(&state.db, user_id).await?;
+
+    let notice = Email {
+        to: updated_user.email.clone(),
+        subject: "Your password was changed".to_string(),
+        body: format!(
+            "The password for your account {} was just changed, your other devices were logged out \
+            and your API keys revoked.\n\n\
+            If it wasn't you, request a reset right away with POST /password/forgot and your email address.",
+            updated_user.username
+        ),
+    };
 
+    if let Err(error) = state.mailer.send(notice).await {
+        eprintln!("{}", error);
+    }
 
+    match tokens {
+        Some(tokens) => Ok(HttpResponse::Ok().json(LoginResponse {
+            tokens,
+            user: updated_user,
+        })),
+        None => Ok(HttpResponse::Ok().json(updated_user)),
+    }
+}